@@ -0,0 +1,43 @@
+// Copyright 2017 Jeremy Letang.
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+
+//! Embed the sql of the `migrations` directory in the binary, see
+//! `src/migrate.rs`.
+
+use std::env;
+use std::fs::{self, File};
+use std::io::Write;
+use std::path::Path;
+
+fn main() {
+    let root = env::var("CARGO_MANIFEST_DIR").unwrap();
+    let dir = Path::new(&*root).join("migrations");
+    println!("cargo:rerun-if-changed={}", dir.display());
+
+    let mut migrations = fs::read_dir(&dir)
+        .unwrap()
+        .map(|e| e.unwrap().path())
+        .filter(|p| p.join("up.sql").is_file())
+        .collect::<Vec<_>>();
+    // the names start with the version, a timestamp
+    migrations.sort();
+
+    let out = Path::new(&*env::var("OUT_DIR").unwrap()).join("migrations.rs");
+    let mut f = File::create(&out).unwrap();
+    writeln!(f, "pub const MIGRATIONS: &'static [Migration] = &[").unwrap();
+    for p in migrations {
+        let up = p.join("up.sql");
+        let down = p.join("down.sql");
+        println!("cargo:rerun-if-changed={}", up.display());
+        println!("cargo:rerun-if-changed={}", down.display());
+        writeln!(f, "    Migration {{ name: {:?}, up: include_str!({:?}), down: include_str!({:?}) }},",
+                 p.file_name().unwrap().to_str().unwrap(),
+                 up.to_str().unwrap(),
+                 down.to_str().unwrap()).unwrap();
+    }
+    writeln!(f, "];").unwrap();
+}
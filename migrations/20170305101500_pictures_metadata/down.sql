@@ -0,0 +1,12 @@
+ALTER TABLE pictures
+      DROP COLUMN title,
+      DROP COLUMN description,
+      DROP COLUMN date_taken,
+      DROP COLUMN owner_nsid,
+      DROP COLUMN owner_realname,
+      DROP COLUMN page_url,
+      DROP COLUMN latitude,
+      DROP COLUMN longitude,
+      DROP COLUMN tags,
+      DROP COLUMN original_width,
+      DROP COLUMN original_height;
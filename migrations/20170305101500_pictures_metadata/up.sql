@@ -0,0 +1,12 @@
+ALTER TABLE pictures
+      ADD COLUMN title           TEXT      DEFAULT NULL,
+      ADD COLUMN description     TEXT      DEFAULT NULL,
+      ADD COLUMN date_taken      TIMESTAMP DEFAULT NULL,
+      ADD COLUMN owner_nsid      TEXT      DEFAULT NULL,
+      ADD COLUMN owner_realname  TEXT      DEFAULT NULL,
+      ADD COLUMN page_url        TEXT      DEFAULT NULL,
+      ADD COLUMN latitude        REAL      DEFAULT NULL,
+      ADD COLUMN longitude       REAL      DEFAULT NULL,
+      ADD COLUMN tags            TEXT[]    DEFAULT '{}' NOT NULL,
+      ADD COLUMN original_width  INT       DEFAULT NULL,
+      ADD COLUMN original_height INT       DEFAULT NULL;
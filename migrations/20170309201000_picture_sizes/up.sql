@@ -0,0 +1,16 @@
+CREATE TABLE IF NOT EXISTS picture_sizes
+(
+  id          VARCHAR(36) PRIMARY KEY NOT NULL,
+  picture_id  VARCHAR(36) NOT NULL,
+  label       TEXT NOT NULL,
+  width       INT NOT NULL,
+  height      INT NOT NULL,
+  url         TEXT NOT NULL,
+
+  created_at TIMESTAMP DEFAULT CURRENT_TIMESTAMP NOT NULL,
+  updated_at TIMESTAMP DEFAULT CURRENT_TIMESTAMP NOT NULL,
+
+  UNIQUE (picture_id, label)
+);
+
+ALTER TABLE picture_sizes ADD FOREIGN KEY (picture_id) REFERENCES pictures (id);
@@ -0,0 +1,7 @@
+DROP INDEX picture_sizes_sha256_idx;
+
+ALTER TABLE picture_sizes
+      DROP COLUMN sha256,
+      DROP COLUMN file_size,
+      DROP COLUMN mime_type,
+      DROP COLUMN mirrored_at;
@@ -0,0 +1,7 @@
+ALTER TABLE picture_sizes
+      ADD COLUMN sha256      VARCHAR(64) DEFAULT NULL,
+      ADD COLUMN file_size   BIGINT      DEFAULT NULL,
+      ADD COLUMN mime_type   TEXT        DEFAULT NULL,
+      ADD COLUMN mirrored_at TIMESTAMP   DEFAULT NULL;
+
+CREATE INDEX picture_sizes_sha256_idx ON picture_sizes (sha256);
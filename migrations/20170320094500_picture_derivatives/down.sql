@@ -0,0 +1,5 @@
+DROP TABLE picture_derivatives;
+
+ALTER TABLE pictures
+      DROP COLUMN blurhash,
+      DROP COLUMN dominant_color;
@@ -0,0 +1,22 @@
+ALTER TABLE pictures
+      ADD COLUMN blurhash       TEXT       DEFAULT NULL,
+      ADD COLUMN dominant_color VARCHAR(7) DEFAULT NULL;
+
+CREATE TABLE IF NOT EXISTS picture_derivatives
+(
+  id          VARCHAR(36) PRIMARY KEY NOT NULL,
+  picture_id  VARCHAR(36) NOT NULL,
+  name        TEXT NOT NULL,
+  width       INT NOT NULL,
+  height      INT NOT NULL,
+  sha256      VARCHAR(64) NOT NULL,
+  file_size   BIGINT NOT NULL,
+  mime_type   TEXT NOT NULL,
+
+  created_at TIMESTAMP DEFAULT CURRENT_TIMESTAMP NOT NULL,
+  updated_at TIMESTAMP DEFAULT CURRENT_TIMESTAMP NOT NULL,
+
+  UNIQUE (picture_id, name)
+);
+
+ALTER TABLE picture_derivatives ADD FOREIGN KEY (picture_id) REFERENCES pictures (id);
@@ -0,0 +1,3 @@
+ALTER TABLE pictures
+      DROP COLUMN phash,
+      DROP COLUMN duplicate_of;
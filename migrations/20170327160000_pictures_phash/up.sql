@@ -0,0 +1,5 @@
+ALTER TABLE pictures
+      ADD COLUMN phash        BIGINT      DEFAULT NULL,
+      ADD COLUMN duplicate_of VARCHAR(36) DEFAULT NULL;
+
+ALTER TABLE pictures ADD FOREIGN KEY (duplicate_of) REFERENCES pictures (id);
@@ -0,0 +1,5 @@
+ALTER TABLE pictures
+      DROP COLUMN status,
+      DROP COLUMN status_reason,
+      DROP COLUMN status_changed_at,
+      DROP COLUMN verified_at;
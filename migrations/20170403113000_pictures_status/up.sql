@@ -0,0 +1,5 @@
+ALTER TABLE pictures
+      ADD COLUMN status            VARCHAR(16) DEFAULT 'active' NOT NULL,
+      ADD COLUMN status_reason     TEXT        DEFAULT NULL,
+      ADD COLUMN status_changed_at TIMESTAMP   DEFAULT NULL,
+      ADD COLUMN verified_at       TIMESTAMP   DEFAULT NULL;
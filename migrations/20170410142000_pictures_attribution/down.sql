@@ -0,0 +1,3 @@
+ALTER TABLE pictures
+      DROP COLUMN attribution_text,
+      DROP COLUMN attribution_html;
@@ -0,0 +1,3 @@
+ALTER TABLE pictures
+      ADD COLUMN attribution_text TEXT DEFAULT NULL,
+      ADD COLUMN attribution_html TEXT DEFAULT NULL;
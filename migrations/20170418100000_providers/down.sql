@@ -0,0 +1,20 @@
+-- the foreign keys to the pictures do not cascade at this version
+DELETE FROM picture_sizes
+      WHERE picture_id IN (SELECT id FROM pictures WHERE provider != 'flickr');
+DELETE FROM picture_derivatives
+      WHERE picture_id IN (SELECT id FROM pictures WHERE provider != 'flickr');
+UPDATE pictures SET duplicate_of = NULL
+      WHERE duplicate_of IN (SELECT id FROM pictures WHERE provider != 'flickr');
+DELETE FROM pictures WHERE provider != 'flickr';
+DELETE FROM licenses WHERE provider != 'flickr';
+
+ALTER TABLE licenses
+      DROP CONSTRAINT licenses_provider_name_key,
+      DROP COLUMN provider,
+      ALTER COLUMN flickr_id SET NOT NULL;
+
+ALTER TABLE pictures
+      DROP CONSTRAINT pictures_provider_external_id_key,
+      DROP COLUMN provider;
+ALTER TABLE pictures RENAME COLUMN external_id TO flickr_id;
+ALTER TABLE pictures ADD UNIQUE (flickr_id);
@@ -0,0 +1,10 @@
+ALTER TABLE pictures RENAME COLUMN flickr_id TO external_id;
+ALTER TABLE pictures
+      DROP CONSTRAINT pictures_flickr_id_key,
+      ADD COLUMN provider VARCHAR(32) DEFAULT 'flickr' NOT NULL,
+      ADD UNIQUE (provider, external_id);
+
+ALTER TABLE licenses
+      ALTER COLUMN flickr_id DROP NOT NULL,
+      ADD COLUMN provider VARCHAR(32) DEFAULT 'flickr' NOT NULL,
+      ADD UNIQUE (provider, name);
@@ -0,0 +1,20 @@
+-- keyed by provider and external id rather than by picture so a rejected
+-- picture stays rejected even once its row is deleted
+CREATE TABLE IF NOT EXISTS picture_curation
+(
+  id          VARCHAR(36) PRIMARY KEY NOT NULL,
+  provider    VARCHAR(32) NOT NULL,
+  external_id VARCHAR(36) NOT NULL,
+  monument_id VARCHAR(36) NOT NULL,
+  pinned      BOOLEAN DEFAULT FALSE NOT NULL,
+  rejected    BOOLEAN DEFAULT FALSE NOT NULL,
+  sort_order  INT  DEFAULT NULL,
+  note        TEXT DEFAULT NULL,
+
+  created_at TIMESTAMP DEFAULT CURRENT_TIMESTAMP NOT NULL,
+  updated_at TIMESTAMP DEFAULT CURRENT_TIMESTAMP NOT NULL,
+
+  UNIQUE (provider, external_id)
+);
+
+ALTER TABLE picture_curation ADD FOREIGN KEY (monument_id) REFERENCES monuments (id);
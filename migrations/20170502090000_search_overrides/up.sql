@@ -0,0 +1,14 @@
+-- keyed by the unesco id number so overrides can be written before the
+-- monument is imported
+CREATE TABLE IF NOT EXISTS search_overrides
+(
+  id          VARCHAR(36) PRIMARY KEY NOT NULL,
+  id_number   INT UNIQUE NOT NULL,
+  query       TEXT   DEFAULT NULL,
+  tags        TEXT[] DEFAULT '{}' NOT NULL,
+  bbox        TEXT   DEFAULT NULL,
+  group_id    TEXT   DEFAULT NULL,
+
+  created_at TIMESTAMP DEFAULT CURRENT_TIMESTAMP NOT NULL,
+  updated_at TIMESTAMP DEFAULT CURRENT_TIMESTAMP NOT NULL
+);
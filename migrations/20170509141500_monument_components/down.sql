@@ -0,0 +1 @@
+DROP TABLE monument_components;
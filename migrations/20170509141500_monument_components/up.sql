@@ -0,0 +1,18 @@
+-- the component sites of the serial nominations
+CREATE TABLE IF NOT EXISTS monument_components
+(
+  id            VARCHAR(36) PRIMARY KEY NOT NULL,
+  monument_id   VARCHAR(36) NOT NULL REFERENCES monuments (id),
+  component_ref TEXT   DEFAULT NULL,
+  name          TEXT   NOT NULL,
+  latitude      REAL   DEFAULT NULL,
+  longitude     REAL   DEFAULT NULL,
+  -- in hectares
+  area          REAL   DEFAULT NULL,
+
+  created_at TIMESTAMP DEFAULT CURRENT_TIMESTAMP NOT NULL,
+  updated_at TIMESTAMP DEFAULT CURRENT_TIMESTAMP NOT NULL,
+  UNIQUE (monument_id, name)
+);
+
+CREATE INDEX monument_components_monument_id_idx ON monument_components (monument_id);
@@ -0,0 +1 @@
+ALTER TABLE last_updates DROP COLUMN empty_runs;
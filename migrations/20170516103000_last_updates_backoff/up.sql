@@ -0,0 +1,2 @@
+-- number of refreshes in a row which found no new picture
+ALTER TABLE last_updates ADD COLUMN empty_runs INT DEFAULT 0 NOT NULL;
@@ -0,0 +1,28 @@
+-- freshness of each enrichment task of the monuments
+CREATE TABLE IF NOT EXISTS task_runs
+(
+  id              VARCHAR(36) PRIMARY KEY NOT NULL,
+  monument_id     VARCHAR(36) NOT NULL REFERENCES monuments (id),
+  task            TEXT NOT NULL,
+  last_success_at TIMESTAMP DEFAULT NULL,
+  last_attempt_at TIMESTAMP DEFAULT NULL,
+  last_error      TEXT DEFAULT NULL,
+  -- number of successful runs in a row which found nothing new
+  empty_runs      INT DEFAULT 0 NOT NULL,
+
+  created_at TIMESTAMP DEFAULT CURRENT_TIMESTAMP NOT NULL,
+  updated_at TIMESTAMP DEFAULT CURRENT_TIMESTAMP NOT NULL,
+  UNIQUE (monument_id, task)
+);
+
+-- the picture harvest was tracked by last_updates, which were created
+-- backdated so they never count as a success
+INSERT INTO task_runs (id, monument_id, task, last_success_at, last_attempt_at,
+                       empty_runs, created_at, updated_at)
+SELECT DISTINCT ON (monument_id)
+       id, monument_id, 'pictures',
+       CASE WHEN updated_at > created_at THEN updated_at ELSE NULL END,
+       CASE WHEN updated_at > created_at THEN updated_at ELSE NULL END,
+       empty_runs, created_at, CURRENT_TIMESTAMP
+FROM last_updates
+ORDER BY monument_id, updated_at DESC;
@@ -0,0 +1,4 @@
+ALTER TABLE monuments DROP COLUMN historical_description_html;
+ALTER TABLE monuments DROP COLUMN justification_html;
+ALTER TABLE monuments DROP COLUMN long_description_html;
+ALTER TABLE monuments DROP COLUMN short_description_html;
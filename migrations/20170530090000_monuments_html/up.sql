@@ -0,0 +1,4 @@
+ALTER TABLE monuments ADD COLUMN short_description_html TEXT;
+ALTER TABLE monuments ADD COLUMN long_description_html TEXT;
+ALTER TABLE monuments ADD COLUMN justification_html TEXT;
+ALTER TABLE monuments ADD COLUMN historical_description_html TEXT;
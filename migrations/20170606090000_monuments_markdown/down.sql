@@ -0,0 +1,4 @@
+ALTER TABLE monuments DROP COLUMN historical_description_markdown;
+ALTER TABLE monuments DROP COLUMN justification_markdown;
+ALTER TABLE monuments DROP COLUMN long_description_markdown;
+ALTER TABLE monuments DROP COLUMN short_description_markdown;
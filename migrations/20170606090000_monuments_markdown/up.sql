@@ -0,0 +1,5 @@
+-- commonmark rendering of the html descriptions of the whc
+ALTER TABLE monuments ADD COLUMN short_description_markdown TEXT;
+ALTER TABLE monuments ADD COLUMN long_description_markdown TEXT;
+ALTER TABLE monuments ADD COLUMN justification_markdown TEXT;
+ALTER TABLE monuments ADD COLUMN historical_description_markdown TEXT;
@@ -0,0 +1,47 @@
+ALTER TABLE task_runs
+      DROP CONSTRAINT task_runs_monument_id_fkey,
+      ADD CONSTRAINT task_runs_monument_id_fkey
+          FOREIGN KEY (monument_id) REFERENCES monuments (id);
+
+ALTER TABLE monument_components
+      DROP CONSTRAINT monument_components_monument_id_fkey,
+      ADD CONSTRAINT monument_components_monument_id_fkey
+          FOREIGN KEY (monument_id) REFERENCES monuments (id);
+
+ALTER TABLE picture_curation
+      DROP CONSTRAINT picture_curation_monument_id_fkey,
+      ADD CONSTRAINT picture_curation_monument_id_fkey
+          FOREIGN KEY (monument_id) REFERENCES monuments (id);
+
+ALTER TABLE picture_derivatives
+      DROP CONSTRAINT picture_derivatives_picture_id_fkey,
+      ADD CONSTRAINT picture_derivatives_picture_id_fkey
+          FOREIGN KEY (picture_id) REFERENCES pictures (id);
+
+ALTER TABLE picture_sizes
+      DROP CONSTRAINT picture_sizes_picture_id_fkey,
+      ADD CONSTRAINT picture_sizes_picture_id_fkey
+          FOREIGN KEY (picture_id) REFERENCES pictures (id);
+
+ALTER TABLE last_updates
+      DROP CONSTRAINT last_updates_monument_id_fkey,
+      ADD CONSTRAINT last_updates_monument_id_fkey
+          FOREIGN KEY (monument_id) REFERENCES monuments (id);
+
+ALTER TABLE pictures
+      DROP CONSTRAINT pictures_duplicate_of_fkey,
+      DROP CONSTRAINT pictures_license_id_fkey,
+      DROP CONSTRAINT pictures_monument_id_fkey,
+      ADD CONSTRAINT pictures_duplicate_of_fkey
+          FOREIGN KEY (duplicate_of) REFERENCES pictures (id),
+      ADD CONSTRAINT pictures_license_id_fkey
+          FOREIGN KEY (license_id) REFERENCES licenses (id),
+      ADD CONSTRAINT pictures_monument_id_fkey
+          FOREIGN KEY (monument_id) REFERENCES monuments (id);
+
+DROP INDEX picture_curation_monument_id_idx;
+DROP INDEX pictures_duplicate_of_idx;
+DROP INDEX pictures_license_id_idx;
+DROP INDEX pictures_monument_id_idx;
+
+ALTER TABLE last_updates DROP CONSTRAINT last_updates_monument_id_key;
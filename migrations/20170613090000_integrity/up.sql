@@ -0,0 +1,60 @@
+-- a monument has a single last update, the duplicates are merged by
+-- `whlp repair last-updates` which works on the previous schema
+DO $$
+BEGIN
+    IF EXISTS (SELECT monument_id FROM last_updates GROUP BY monument_id HAVING COUNT(*) > 1) THEN
+        RAISE EXCEPTION 'duplicate last_updates, run whlp repair last-updates first';
+    END IF;
+END
+$$;
+
+ALTER TABLE last_updates ADD CONSTRAINT last_updates_monument_id_key UNIQUE (monument_id);
+
+CREATE INDEX pictures_monument_id_idx ON pictures (monument_id);
+CREATE INDEX pictures_license_id_idx ON pictures (license_id);
+CREATE INDEX pictures_duplicate_of_idx ON pictures (duplicate_of);
+CREATE INDEX picture_curation_monument_id_idx ON picture_curation (monument_id);
+
+-- deleting a monument deletes everything about it, deleting a picture its
+-- files and marks its duplicates as originals, a used license cannot be
+-- deleted
+ALTER TABLE pictures
+      DROP CONSTRAINT pictures_monument_id_fkey,
+      DROP CONSTRAINT pictures_license_id_fkey,
+      DROP CONSTRAINT pictures_duplicate_of_fkey,
+      ADD CONSTRAINT pictures_monument_id_fkey
+          FOREIGN KEY (monument_id) REFERENCES monuments (id) ON DELETE CASCADE,
+      ADD CONSTRAINT pictures_license_id_fkey
+          FOREIGN KEY (license_id) REFERENCES licenses (id) ON DELETE RESTRICT,
+      ADD CONSTRAINT pictures_duplicate_of_fkey
+          FOREIGN KEY (duplicate_of) REFERENCES pictures (id) ON DELETE SET NULL;
+
+ALTER TABLE last_updates
+      DROP CONSTRAINT last_updates_monument_id_fkey,
+      ADD CONSTRAINT last_updates_monument_id_fkey
+          FOREIGN KEY (monument_id) REFERENCES monuments (id) ON DELETE CASCADE;
+
+ALTER TABLE picture_sizes
+      DROP CONSTRAINT picture_sizes_picture_id_fkey,
+      ADD CONSTRAINT picture_sizes_picture_id_fkey
+          FOREIGN KEY (picture_id) REFERENCES pictures (id) ON DELETE CASCADE;
+
+ALTER TABLE picture_derivatives
+      DROP CONSTRAINT picture_derivatives_picture_id_fkey,
+      ADD CONSTRAINT picture_derivatives_picture_id_fkey
+          FOREIGN KEY (picture_id) REFERENCES pictures (id) ON DELETE CASCADE;
+
+ALTER TABLE picture_curation
+      DROP CONSTRAINT picture_curation_monument_id_fkey,
+      ADD CONSTRAINT picture_curation_monument_id_fkey
+          FOREIGN KEY (monument_id) REFERENCES monuments (id) ON DELETE CASCADE;
+
+ALTER TABLE monument_components
+      DROP CONSTRAINT monument_components_monument_id_fkey,
+      ADD CONSTRAINT monument_components_monument_id_fkey
+          FOREIGN KEY (monument_id) REFERENCES monuments (id) ON DELETE CASCADE;
+
+ALTER TABLE task_runs
+      DROP CONSTRAINT task_runs_monument_id_fkey,
+      ADD CONSTRAINT task_runs_monument_id_fkey
+          FOREIGN KEY (monument_id) REFERENCES monuments (id) ON DELETE CASCADE;
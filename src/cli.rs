@@ -0,0 +1,434 @@
+// Copyright 2017 Jeremy Letang.
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+
+//! The command line: the database and http options are shared, then each
+//! step of the import is a subcommand with its own options, `run-all`
+//! running every step in sequence.
+//!
+//! The options not given fall back on the settings of the config file and
+//! the `WHLP_*` environment variables, see `config`.
+
+use clap::{self, App, Arg, ArgMatches, SubCommand};
+use config::{self, Config};
+use curation;
+use flickr_api::HARVESTED_LICENSES;
+use migrate;
+use repair;
+use std::str::FromStr;
+
+/// options to import the monuments
+#[derive(Clone, Debug, PartialEq)]
+pub struct SyncOptions {
+    pub xml: Option<String>,
+    pub components: Option<String>,
+    pub search_overrides: Option<String>,
+    /// commit each batch of monuments instead of the whole sync at once
+    pub batch_commit: bool,
+    /// keep a sanitized html copy of the descriptions
+    pub keep_html: bool,
+}
+
+/// options to search, fetch and check the pictures
+#[derive(Clone, Debug, PartialEq)]
+pub struct HarvestOptions {
+    pub flickr_key: Option<String>,
+    pub commons: bool,
+    pub commons_api: String,
+    pub provider_fixtures: Vec<String>,
+    /// flickr license ids of the pictures harvested
+    pub flickr_licenses: Vec<i32>,
+    pub refresh_days: i64,
+    pub refresh_policy: Option<String>,
+    pub force: Vec<i32>,
+    pub backfill_pictures: bool,
+    pub verify_pictures: bool,
+    pub verify_action: String,
+}
+
+/// options to download and process the picture files
+#[derive(Clone, Debug, PartialEq)]
+pub struct MediaOptions {
+    pub mirror_dir: Option<String>,
+    pub mirror_sizes: Vec<String>,
+    pub mirror_workers: usize,
+    pub derivatives: bool,
+    pub derivatives_profile: Option<String>,
+    pub dedupe: bool,
+    pub dedupe_threshold: u32,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum Command {
+    Migrate(migrate::Command),
+    SyncMonuments(SyncOptions),
+    SyncLicenses { flickr_key: String },
+    HarvestPictures(HarvestOptions, MediaOptions),
+    CleanHtml { keep_html: bool },
+    Export { output: Option<String> },
+    Stats,
+    RunAll {
+        sync: SyncOptions,
+        harvest: HarvestOptions,
+        media: MediaOptions,
+    },
+    Curation(curation::Command),
+    Repair(repair::Command),
+    /// print the effective config
+    ConfigCheck(Config),
+}
+
+pub struct CmdLineArgs {
+    /// none only for the commands which do not use the database
+    pub pq_addr: Option<String>,
+    pub http_record: Option<String>,
+    pub http_replay: Option<String>,
+    /// only report the changes, without writing them
+    pub dry_run: bool,
+    pub command: Command,
+}
+
+pub fn dry_run_arg<'a, 'b>() -> Arg<'a, 'b> {
+    Arg::with_name("dry-run")
+        .long("dry-run")
+        .help("do every read and api call but only print the changes instead of saving them")
+}
+
+fn keep_html_arg<'a, 'b>() -> Arg<'a, 'b> {
+    Arg::with_name("keep-html")
+        .long("keep-html")
+        .help("keep a sanitized html copy of the descriptions next to their text")
+}
+
+fn flickr_key_arg<'a, 'b>() -> Arg<'a, 'b> {
+    Arg::with_name("flickr-key")
+        .long("flickr-key")
+        .help("flicker api key to list pictures of the monuments")
+        .takes_value(true)
+}
+
+fn sync_args<'a, 'b>() -> Vec<Arg<'a, 'b>> {
+    vec![
+        Arg::with_name("xml")
+            .long("xml")
+            .help("use local whc xml file")
+            .takes_value(true),
+        Arg::with_name("components")
+            .long("components")
+            .help("json file of the component sites of the serial nominations, by id number")
+            .takes_value(true),
+        Arg::with_name("search-overrides")
+            .long("search-overrides")
+            .help("json file of custom picture searches for the monuments, by id number")
+            .takes_value(true),
+        Arg::with_name("batch-commit")
+            .long("batch-commit")
+            .help("commit the monuments by batches, keeping the saved batches if one fails"),
+        keep_html_arg(),
+    ]
+}
+
+fn harvest_args<'a, 'b>() -> Vec<Arg<'a, 'b>> {
+    vec![
+        flickr_key_arg(),
+        Arg::with_name("commons")
+            .long("commons")
+            .help("also list pictures of the monuments from wikimedia commons"),
+        Arg::with_name("no-commons")
+            .long("no-commons")
+            .help("do not list pictures from wikimedia commons, even if the config does")
+            .conflicts_with("commons"),
+        Arg::with_name("commons-api")
+            .long("commons-api")
+            .help("url of the mediawiki api used to query commons, the one of wikimedia by default")
+            .takes_value(true),
+        Arg::with_name("provider-fixtures")
+            .long("provider-fixtures")
+            .help("json file of pictures to harvest instead of calling a real provider")
+            .takes_value(true)
+            .multiple(true)
+            .number_of_values(1),
+        Arg::with_name("refresh-days")
+            .long("refresh-days")
+            .help("number of days before searching again the pictures of a monument, 7 by default")
+            .takes_value(true)
+            .validator(|v| {
+                v.parse().map_err(|_| format!("must be a positive integer"))
+                    .and_then(config::check_refresh_days)
+            }),
+        Arg::with_name("refresh-policy")
+            .long("refresh-policy")
+            .help("json file of refresh windows by category or id number of monument")
+            .takes_value(true),
+        Arg::with_name("force")
+            .long("force")
+            .help("comma separated id numbers of monuments to refresh whatever their last refresh")
+            .takes_value(true),
+        Arg::with_name("flickr-licenses")
+            .long("flickr-licenses")
+            .help("comma separated ids of the flickr licenses harvested, all the free ones by default")
+            .takes_value(true),
+        Arg::with_name("backfill-pictures")
+            .long("backfill-pictures")
+            .help("fetch the metadata of the pictures saved without them"),
+        Arg::with_name("verify-pictures")
+            .long("verify-pictures")
+            .help("check that the saved pictures are still online with the same license"),
+        Arg::with_name("verify-action")
+            .long("verify-action")
+            .help("what to do with the pictures which are not available anymore: mark \
+                   records their status, reason and date; purge also deletes their sizes \
+                   and derivatives and the duplicate links to them, the picture row is kept")
+            .takes_value(true)
+            .possible_values(&["mark", "purge"])
+            .default_value("mark"),
+    ]
+}
+
+fn media_args<'a, 'b>() -> Vec<Arg<'a, 'b>> {
+    vec![
+        Arg::with_name("mirror-dir")
+            .long("mirror-dir")
+            .help("download the pictures in this local directory")
+            .takes_value(true),
+        Arg::with_name("mirror-sizes")
+            .long("mirror-sizes")
+            .help("comma separated flickr size labels to download, Large,Original by default")
+            .takes_value(true),
+        Arg::with_name("mirror-workers")
+            .long("mirror-workers")
+            .help("number of pictures downloaded at the same time, 1 by default")
+            .takes_value(true),
+        Arg::with_name("derivatives")
+            .long("derivatives")
+            .help("generate thumbnails and placeholders of the mirrored pictures"),
+        Arg::with_name("derivatives-profile")
+            .long("derivatives-profile")
+            .help("json file describing the derivatives to generate")
+            .takes_value(true)
+            .requires("derivatives"),
+        Arg::with_name("dedupe")
+            .long("dedupe")
+            .help("flag near-duplicate pictures of each monument"),
+        Arg::with_name("dedupe-threshold")
+            .long("dedupe-threshold")
+            .help("maximum number of different bits between two duplicate pictures hashes")
+            .takes_value(true)
+            .default_value("6"),
+    ]
+}
+
+pub fn parse_cmdline() -> Result<CmdLineArgs, String> {
+    let matches = App::new("whlp")
+        .version("v0.1.0")
+        .global_setting(clap::AppSettings::ColoredHelp)
+        .setting(clap::AppSettings::SubcommandRequiredElseHelp)
+        .about("retrieve information from unesco whc and store them in pq")
+        .arg(Arg::with_name("config")
+             .long("config")
+             .help("toml config file")
+             .takes_value(true))
+        .arg(Arg::with_name("pq-addr")
+             .long("pq-addr")
+             .help("postgres server address")
+             .takes_value(true)
+             .validator(|v| config::check_postgres_url(&*v)))
+        .arg(Arg::with_name("http-record")
+             .long("http-record")
+             .help("save the api responses in this fixtures directory")
+             .takes_value(true)
+             .conflicts_with("http-replay"))
+        .arg(Arg::with_name("http-replay")
+             .long("http-replay")
+             .help("serve the api responses from this fixtures directory instead of the network")
+             .takes_value(true))
+        .subcommand(migrate::subcommand())
+        .subcommand(SubCommand::with_name("sync-monuments")
+                    .about("import the monuments from the whc xml")
+                    .arg(dry_run_arg())
+                    .args(&sync_args()))
+        .subcommand(SubCommand::with_name("sync-licenses")
+                    .about("import the licenses of flickr")
+                    .arg(dry_run_arg())
+                    .arg(flickr_key_arg()))
+        .subcommand(SubCommand::with_name("harvest-pictures")
+                    .about("search, fetch and process the pictures of the monuments")
+                    .arg(dry_run_arg())
+                    .args(&harvest_args())
+                    .args(&media_args()))
+        .subcommand(SubCommand::with_name("clean-html")
+                    .about("convert the html of the monuments texts already imported to text and markdown")
+                    .arg(dry_run_arg())
+                    .arg(keep_html_arg()))
+        .subcommand(SubCommand::with_name("export")
+                    .about("write the monuments and their pictures as json")
+                    .arg(Arg::with_name("output")
+                         .long("output")
+                         .help("file to write, standard output if not set")
+                         .takes_value(true)))
+        .subcommand(SubCommand::with_name("stats")
+                    .about("show what the database contains"))
+        .subcommand(SubCommand::with_name("run-all")
+                    .about("migrate, import the monuments then harvest the pictures")
+                    .arg(dry_run_arg())
+                    .args(&sync_args())
+                    .args(&harvest_args())
+                    .args(&media_args()))
+        .subcommand(curation::subcommand())
+        .subcommand(repair::subcommand())
+        .subcommand(SubCommand::with_name("config")
+                    .about("inspect the settings")
+                    .setting(clap::AppSettings::SubcommandRequiredElseHelp)
+                    .subcommand(SubCommand::with_name("check")
+                                .about("validate the config then print it with the secrets masked")))
+        .get_matches();
+
+    let mut config = match matches.value_of("config") {
+        Some(f) => Config::from_file(f)?,
+        None => Config::default(),
+    };
+    config.apply_env()?;
+    config.validate()?;
+    let mut config = config.with_defaults();
+    config.database.url = value(&matches, "pq-addr", &config.database.url);
+    let pq_addr = config.database.url.clone();
+
+    // only the subcommands which write define the option
+    let dry_run = matches.subcommand().1.map_or(false, |m| m.is_present("dry-run"));
+    let command = match matches.subcommand() {
+        ("migrate", Some(m)) => Command::Migrate(migrate::from_matches(m)?),
+        ("sync-monuments", Some(m)) => Command::SyncMonuments(sync_options(m, &config)),
+        ("sync-licenses", Some(m)) => Command::SyncLicenses {
+            flickr_key: value(m, "flickr-key", &config.flickr.key)
+                .ok_or(format!("missing flickr key, set --flickr-key or flickr.key"))?,
+        },
+        ("harvest-pictures", Some(m)) => {
+            Command::HarvestPictures(harvest_options(m, &config)?, media_options(m, &config)?)
+        },
+        ("clean-html", Some(m)) => Command::CleanHtml { keep_html: m.is_present("keep-html") },
+        ("export", Some(m)) => Command::Export {
+            output: value(m, "output", &config.output.export),
+        },
+        ("stats", Some(_)) => Command::Stats,
+        ("run-all", Some(m)) => Command::RunAll {
+            sync: sync_options(m, &config),
+            harvest: harvest_options(m, &config)?,
+            media: media_options(m, &config)?,
+        },
+        ("curation", Some(m)) => Command::Curation(curation::from_matches(m)?),
+        ("repair", Some(m)) => Command::Repair(repair::from_matches(m)?),
+        ("config", Some(_)) => Command::ConfigCheck(config),
+        _ => return Err(format!("missing command, see --help")),
+    };
+
+    match command {
+        Command::ConfigCheck(_) => {},
+        _ if pq_addr.is_none() => {
+            return Err(format!("missing postgres address, set --pq-addr, database.url or WHLP_DATABASE_URL"))
+        },
+        _ => {},
+    }
+
+    Ok(CmdLineArgs {
+        pq_addr: pq_addr,
+        http_record: matches.value_of("http-record").map_or(None, |s| Some(s.into())),
+        http_replay: matches.value_of("http-replay").map_or(None, |s| Some(s.into())),
+        dry_run: dry_run,
+        command: command,
+    })
+}
+
+/// the value given on the command line, else the one of the config
+fn value(m: &ArgMatches, name: &str, configured: &Option<String>) -> Option<String> {
+    m.value_of(name).map(|s| s.to_string()).or(configured.clone())
+}
+
+/// a comma separated list given on the command line, else the one of the
+/// config
+fn values<T: FromStr + Clone>(m: &ArgMatches, name: &str, configured: &Option<Vec<T>>)
+                              -> Result<Vec<T>, String> {
+    match m.value_of(name) {
+        Some(v) => v.split(',')
+            .map(|s| s.trim().parse::<T>())
+            .collect::<Result<Vec<_>, _>>()
+            .map_err(|_| format!("invalid {}, {}", name, v)),
+        None => Ok(configured.clone().unwrap_or(vec![])),
+    }
+}
+
+fn sync_options(m: &ArgMatches, c: &Config) -> SyncOptions {
+    SyncOptions {
+        xml: value(m, "xml", &c.sources.xml),
+        components: value(m, "components", &c.sources.components),
+        search_overrides: value(m, "search-overrides", &c.sources.search_overrides),
+        batch_commit: m.is_present("batch-commit"),
+        keep_html: m.is_present("keep-html"),
+    }
+}
+
+fn harvest_options(m: &ArgMatches, c: &Config) -> Result<HarvestOptions, String> {
+    let refresh_days = match m.value_of("refresh-days") {
+        Some(v) => v.parse().map_err(|_| format!("refresh-days must be a positive integer"))?,
+        None => c.freshness.refresh_days.unwrap(),
+    };
+    let flickr_key = value(m, "flickr-key", &c.flickr.key);
+    if flickr_key.is_none() && (m.is_present("backfill-pictures") || m.is_present("verify-pictures")) {
+        return Err(format!("backfill-pictures and verify-pictures need a flickr key"))
+    }
+
+    // the licenses given on the command line are checked as the ones of the
+    // config are
+    let flickr_licenses = values(m, "flickr-licenses", &c.licenses.flickr)?;
+    if flickr_licenses.is_empty() {
+        return Err(format!("flickr-licenses: no license"))
+    }
+    match flickr_licenses.iter().find(|l| !HARVESTED_LICENSES.contains(l)) {
+        Some(l) => return Err(format!("flickr-licenses: {} is not a free license", l)),
+        None => {},
+    }
+
+    Ok(HarvestOptions {
+        flickr_key: flickr_key,
+        commons: !m.is_present("no-commons") && (m.is_present("commons") || c.sources.commons.unwrap()),
+        commons_api: value(m, "commons-api", &c.sources.commons_api).unwrap(),
+        provider_fixtures: match m.values_of("provider-fixtures") {
+            Some(v) => v.map(|s| s.to_string()).collect(),
+            None => c.sources.provider_fixtures.clone().unwrap(),
+        },
+        flickr_licenses: flickr_licenses,
+        refresh_days: refresh_days,
+        refresh_policy: value(m, "refresh-policy", &c.freshness.refresh_policy),
+        force: values(m, "force", &None)?,
+        backfill_pictures: m.is_present("backfill-pictures"),
+        verify_pictures: m.is_present("verify-pictures"),
+        verify_action: m.value_of("verify-action").unwrap().into(),
+    })
+}
+
+fn media_options(m: &ArgMatches, c: &Config) -> Result<MediaOptions, String> {
+    let mirror_workers = match m.value_of("mirror-workers") {
+        Some(v) => v.parse().map_err(|_| format!("mirror-workers must be a positive integer"))?,
+        None => c.concurrency.mirror_workers.unwrap(),
+    };
+    if mirror_workers == 0 {
+        return Err(format!("mirror-workers must be a positive integer"))
+    }
+    let mirror_dir = value(m, "mirror-dir", &c.output.mirror_dir);
+    if mirror_dir.is_none() && m.is_present("derivatives") {
+        return Err(format!("derivatives need a mirror directory"))
+    }
+
+    Ok(MediaOptions {
+        mirror_dir: mirror_dir,
+        mirror_sizes: values(m, "mirror-sizes", &c.output.mirror_sizes)?,
+        mirror_workers: mirror_workers,
+        derivatives: m.is_present("derivatives"),
+        derivatives_profile: value(m, "derivatives-profile", &c.output.derivatives_profile),
+        dedupe: m.is_present("dedupe"),
+        dedupe_threshold: m.value_of("dedupe-threshold").unwrap().parse()
+            .map_err(|_| format!("dedupe-threshold must be a positive integer"))?,
+    })
+}
@@ -0,0 +1,165 @@
+// Copyright 2017 Jeremy Letang.
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+
+//! Client of the MediaWiki api of Wikimedia Commons.
+
+use http;
+use reqwest::Url;
+use serde_json::Value;
+use std::collections::HashMap;
+
+pub const COMMONS_API: &'static str = "https://commons.wikimedia.org/w/api.php";
+
+/// the namespace of the files in mediawiki
+const FILE_NAMESPACE: &'static str = "6";
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct Page {
+    pub pageid: i64,
+    pub title: String,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct CategoryMembers {
+    #[serde(default)]
+    pub categorymembers: Vec<Page>,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct CategoryMembersPayload {
+    pub query: Option<CategoryMembers>,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct GeoSearch {
+    #[serde(default)]
+    pub geosearch: Vec<Page>,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct GeoSearchPayload {
+    pub query: Option<GeoSearch>,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct MetadataValue {
+    pub value: Value,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct ImageInfo {
+    pub user: String,
+    pub url: String,
+    pub descriptionurl: String,
+    pub width: i32,
+    pub height: i32,
+    pub thumburl: Option<String>,
+    pub thumbwidth: Option<i32>,
+    pub thumbheight: Option<i32>,
+    #[serde(default)]
+    pub extmetadata: HashMap<String, MetadataValue>,
+}
+
+impl ImageInfo {
+    /// value of an extmetadata field, as a string
+    pub fn meta(&self, name: &str) -> Option<String> {
+        match self.extmetadata.get(name).map(|m| &m.value) {
+            Some(&Value::String(ref s)) if !s.trim().is_empty() => Some(s.clone()),
+            Some(&Value::Number(ref n)) => Some(format!("{}", n)),
+            _ => None,
+        }
+    }
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct ImagePage {
+    pub pageid: Option<i64>,
+    pub title: String,
+    #[serde(default)]
+    pub missing: bool,
+    #[serde(default)]
+    pub imageinfo: Vec<ImageInfo>,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct ImagePages {
+    pub pages: Vec<ImagePage>,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct ImageInfoPayload {
+    pub query: ImagePages,
+}
+
+fn url(api: &str, params: &[(&str, &str)]) -> Result<String, String> {
+    let mut url = Url::parse(api).map_err(|e| format!("invalid commons api url, {}", e))?;
+    url.query_pairs_mut()
+        .append_pair("action", "query")
+        .append_pair("format", "json")
+        .append_pair("formatversion", "2");
+    for &(k, v) in params {
+        url.query_pairs_mut().append_pair(k, v);
+    }
+    Ok(url.as_str().to_string())
+}
+
+/// files of the category `Category:<category>`
+pub fn category_members(api: &str, category: &str) -> Result<Vec<Page>, String> {
+    info!("calling commons categorymembers api");
+    let cmtitle = format!("Category:{}", category);
+    let url = url(api, &[("list", "categorymembers"),
+                         ("cmtitle", &*cmtitle),
+                         ("cmtype", "file"),
+                         ("cmnamespace", FILE_NAMESPACE),
+                         ("cmlimit", "10")])?;
+    http::get_json::<CategoryMembersPayload>(&*url, "commons category members")
+        .map(|v| v.query.map_or(vec![], |q| q.categorymembers))
+}
+
+/// files located at less than `radius` meters of the coordinates
+pub fn geosearch(api: &str, lat: f32, lng: f32, radius: u32) -> Result<Vec<Page>, String> {
+    info!("calling commons geosearch api");
+    let gscoord = format!("{}|{}", lat, lng);
+    let gsradius = radius.to_string();
+    let url = url(api, &[("list", "geosearch"),
+                         ("gscoord", &*gscoord),
+                         ("gsradius", &*gsradius),
+                         ("gsnamespace", FILE_NAMESPACE),
+                         ("gslimit", "10")])?;
+    http::get_json::<GeoSearchPayload>(&*url, "commons geosearch")
+        .map(|v| v.query.map_or(vec![], |q| q.geosearch))
+}
+
+/// files located in a bounding box
+pub fn geosearch_bbox(api: &str, min_lon: f32, min_lat: f32, max_lon: f32, max_lat: f32)
+                      -> Result<Vec<Page>, String> {
+    info!("calling commons geosearch api with a bounding box");
+    // mediawiki wants top|left|bottom|right
+    let gsbbox = format!("{}|{}|{}|{}", max_lat, min_lon, min_lat, max_lon);
+    let url = url(api, &[("list", "geosearch"),
+                         ("gsbbox", &*gsbbox),
+                         ("gsnamespace", FILE_NAMESPACE),
+                         ("gslimit", "10")])?;
+    http::get_json::<GeoSearchPayload>(&*url, "commons geosearch")
+        .map(|v| v.query.map_or(vec![], |q| q.geosearch))
+}
+
+/// information and license of a file, with a thumbnail of `thumb_width` pixels
+pub fn image_info(api: &str, pageid: &str, thumb_width: u32) -> Result<ImagePage, String> {
+    info!("calling commons imageinfo api");
+    let iiurlwidth = thumb_width.to_string();
+    let url = url(api, &[("prop", "imageinfo"),
+                         ("pageids", pageid),
+                         ("iiprop", "url|size|user|extmetadata"),
+                         ("iiurlwidth", &*iiurlwidth)])?;
+    let mut pages = http::get_json::<ImageInfoPayload>(&*url, "commons image info")?.query.pages;
+    match pages.pop() {
+        Some(ref p) if p.missing || p.imageinfo.is_empty() => Err(format!("commons file {} not found", pageid)),
+        Some(p) => Ok(p),
+        None => Err(format!("commons file {} not found", pageid)),
+    }
+}
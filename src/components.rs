@@ -0,0 +1,103 @@
+// Copyright 2017 Jeremy Letang.
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+
+//! The component sites of the serial nominations, which are not part of the
+//! whc xml, read from a json file keyed by the `id_number` of the sites.
+//! The fields follow the component tables published by the whc.
+//!
+//! ```json
+//! {
+//!   "1178": [
+//!     { "ref": "1178-001", "name": "Mont Saint-Michel",
+//!       "latitude": 48.636, "longitude": -1.511, "area": 6.5 }
+//!   ]
+//! }
+//! ```
+
+use diesel::pg::PgConnection;
+use domain::{self, MonumentComponent};
+use report::{Kind, Report};
+use serde_json;
+use std::collections::HashMap;
+use std::fs::File;
+use std::io::Read;
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct Entry {
+    #[serde(rename = "ref")]
+    pub component_ref: Option<String>,
+    pub name: String,
+    pub latitude: Option<f32>,
+    pub longitude: Option<f32>,
+    /// in hectares
+    pub area: Option<f32>,
+}
+
+pub fn from_file(file: &str) -> Result<HashMap<String, Vec<Entry>>, String> {
+    info!("reading monument components: '{}'", file);
+    let mut buf = String::new();
+    match File::open(file).and_then(|mut f| f.read_to_string(&mut buf)) {
+        Ok(_) => serde_json::from_str::<HashMap<String, Vec<Entry>>>(&*buf)
+            .map_err(|e| format!("invalid monument components, {}", e)),
+        Err(e) => Err(format!("unable to read monument components, {}", e)),
+    }
+}
+
+/// replace the components of the monuments listed in the file
+pub fn import(conn: &PgConnection, file: &str, report: &mut Report) -> Result<(), String> {
+    let mut components_imported = 0;
+
+    for (n, entries) in from_file(file)? {
+        let n = n.parse::<i32>()
+            .map_err(|_| format!("invalid site '{}' in monument components, must be a number", n))?;
+        let (mid, previous) = match domain::dao::find_monument_by_id_number(conn, n) {
+            Some(m) => {
+                let previous = domain::dao::list_monument_components_by_monument_id(conn, &*m.id);
+                (m.id, previous)
+            },
+            // a monument the dry run would insert, it has no component yet
+            None if report.dry_run && report.contains("monuments", Kind::Insert, &*n.to_string()) => {
+                (String::new(), vec![])
+            },
+            None => {
+                warn!("unknown site {} in monument components, skip it", n);
+                continue
+            }
+        };
+        let cs = entries.into_iter()
+            .map(|e| {
+                let mut c = MonumentComponent::new(mid.clone(), e.name);
+                c.component_ref = e.component_ref;
+                c.latitude = e.latitude;
+                c.longitude = e.longitude;
+                c.area = e.area;
+                c
+            })
+            .collect::<Vec<_>>();
+        let same = previous.len() == cs.len() && cs.iter().all(|c| previous.iter().any(|p| {
+            p.name == c.name && p.component_ref == c.component_ref && p.latitude == c.latitude &&
+                p.longitude == c.longitude && p.area == c.area
+        }));
+        if same {
+            continue
+        }
+        for c in &previous {
+            report.delete("monument_components", n.to_string(), &*c.name);
+        }
+        for c in &cs {
+            report.insert("monument_components", n.to_string(), &*c.name);
+        }
+        if !report.dry_run {
+            domain::dao::replace_monument_components(conn, &*mid, &cs)
+                .map_err(|e| format!("unable to save components of site {}, {}", n, e))?;
+        }
+        components_imported += cs.len();
+    }
+
+    info!("{} monument components imported", components_imported);
+    Ok(())
+}
@@ -0,0 +1,286 @@
+// Copyright 2017 Jeremy Letang.
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+
+//! The settings read from the toml file given with `--config`.
+//!
+//! Every setting can be overridden by a `WHLP_<SECTION>_<KEY>` environment
+//! variable, `WHLP_FLICKR_KEY` for `key` in `[flickr]`, the lists being
+//! comma separated. The options of the command line win over both.
+//!
+//! ```toml
+//! [database]
+//! url = "postgres://root:root@pq_db:5432/unesco_whl"
+//!
+//! [sources]
+//! components = "components.json"
+//! commons = true
+//!
+//! [flickr]
+//! key = "..."
+//!
+//! [licenses]
+//! flickr = [4, 5, 9, 10]
+//!
+//! [freshness]
+//! refresh_days = 14
+//!
+//! [concurrency]
+//! mirror_workers = 4
+//!
+//! [output]
+//! mirror_dir = "/data/pictures"
+//! ```
+
+use commons_api::COMMONS_API;
+use flickr_api::HARVESTED_LICENSES;
+use std::env;
+use std::fmt::Display;
+use std::fs::File;
+use std::io::Read;
+use std::path::Path;
+use std::str::FromStr;
+use toml;
+
+const MASK: &'static str = "********";
+
+pub const DEFAULT_REFRESH_DAYS: i64 = 7;
+pub const DEFAULT_MIRROR_WORKERS: usize = 1;
+pub const DEFAULT_MIRROR_SIZES: [&'static str; 2] = ["Large", "Original"];
+
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Database {
+    pub url: Option<String>,
+}
+
+/// where the monuments and their pictures come from
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Sources {
+    pub xml: Option<String>,
+    pub components: Option<String>,
+    pub search_overrides: Option<String>,
+    pub commons: Option<bool>,
+    pub commons_api: Option<String>,
+    pub provider_fixtures: Option<Vec<String>>,
+}
+
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Flickr {
+    pub key: Option<String>,
+}
+
+/// the licenses of the pictures harvested
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Licenses {
+    /// flickr license ids
+    pub flickr: Option<Vec<i32>>,
+}
+
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Freshness {
+    pub refresh_days: Option<i64>,
+    pub refresh_policy: Option<String>,
+}
+
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Concurrency {
+    /// number of pictures downloaded at the same time
+    pub mirror_workers: Option<usize>,
+}
+
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Output {
+    pub mirror_dir: Option<String>,
+    pub mirror_sizes: Option<Vec<String>>,
+    pub derivatives_profile: Option<String>,
+    /// file written by the export
+    pub export: Option<String>,
+}
+
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Config {
+    #[serde(default)]
+    pub database: Database,
+    #[serde(default)]
+    pub sources: Sources,
+    #[serde(default)]
+    pub flickr: Flickr,
+    #[serde(default)]
+    pub licenses: Licenses,
+    #[serde(default)]
+    pub freshness: Freshness,
+    #[serde(default)]
+    pub concurrency: Concurrency,
+    #[serde(default)]
+    pub output: Output,
+}
+
+/// replace `field` by the value of the environment variable `WHLP_<name>`
+fn from_env<T, F>(field: &mut Option<T>, name: &str, parse: F) -> Result<(), String>
+    where F: Fn(&str) -> Result<T, String> {
+    match env::var(format!("WHLP_{}", name)) {
+        Ok(v) => {
+            *field = Some(parse(&*v).map_err(|e| format!("invalid WHLP_{}, {}", name, e))?);
+            Ok(())
+        },
+        Err(_) => Ok(()),
+    }
+}
+
+fn string(v: &str) -> Result<String, String> {
+    Ok(v.to_string())
+}
+
+fn number<T: FromStr>(v: &str) -> Result<T, String> where T::Err: Display {
+    v.trim().parse::<T>().map_err(|e| format!("{}", e))
+}
+
+fn list<T: FromStr>(v: &str) -> Result<Vec<T>, String> where T::Err: Display {
+    v.split(',')
+        .filter(|s| !s.trim().is_empty())
+        .map(|s| number(s))
+        .collect()
+}
+
+/// the url with its password replaced by the mask
+fn mask_url(url: &str) -> String {
+    let start = url.find("://").map_or(0, |i| i + 3);
+    match url[start..].find('@') {
+        Some(at) => {
+            let user_info = &url[start..start + at];
+            match user_info.find(':') {
+                Some(colon) => format!("{}:{}{}", &url[..start + colon], MASK, &url[start + at..]),
+                None => url.to_string(),
+            }
+        },
+        None => url.to_string(),
+    }
+}
+
+fn check_file(errors: &mut Vec<String>, setting: &str, file: &Option<String>) {
+    match *file {
+        Some(ref f) if !Path::new(f).is_file() => errors.push(format!("{}: no file {}", setting, f)),
+        _ => {},
+    }
+}
+
+/// the check of `database.url`, also used for `--pq-addr`
+pub fn check_postgres_url(u: &str) -> Result<(), String> {
+    if u.starts_with("postgres://") || u.starts_with("postgresql://") {
+        Ok(())
+    } else {
+        Err(format!("not a postgres url"))
+    }
+}
+
+/// the check of `freshness.refresh_days`, also used for `--refresh-days`
+pub fn check_refresh_days(d: i64) -> Result<(), String> {
+    if d > 0 { Ok(()) } else { Err(format!("must be positive")) }
+}
+
+impl Config {
+    pub fn from_file(file: &str) -> Result<Config, String> {
+        info!("reading config: '{}'", file);
+        let mut buf = String::new();
+        match File::open(file).and_then(|mut f| f.read_to_string(&mut buf)) {
+            Ok(_) => toml::from_str::<Config>(&*buf)
+                .map_err(|e| format!("invalid config, {}", e)),
+            Err(e) => Err(format!("unable to read config, {}", e)),
+        }
+    }
+
+    /// override the settings with the `WHLP_*` environment variables
+    pub fn apply_env(&mut self) -> Result<(), String> {
+        from_env(&mut self.database.url, "DATABASE_URL", string)?;
+        from_env(&mut self.sources.xml, "SOURCES_XML", string)?;
+        from_env(&mut self.sources.components, "SOURCES_COMPONENTS", string)?;
+        from_env(&mut self.sources.search_overrides, "SOURCES_SEARCH_OVERRIDES", string)?;
+        from_env(&mut self.sources.commons, "SOURCES_COMMONS", number)?;
+        from_env(&mut self.sources.commons_api, "SOURCES_COMMONS_API", string)?;
+        from_env(&mut self.sources.provider_fixtures, "SOURCES_PROVIDER_FIXTURES", list)?;
+        from_env(&mut self.flickr.key, "FLICKR_KEY", string)?;
+        from_env(&mut self.licenses.flickr, "LICENSES_FLICKR", list)?;
+        from_env(&mut self.freshness.refresh_days, "FRESHNESS_REFRESH_DAYS", number)?;
+        from_env(&mut self.freshness.refresh_policy, "FRESHNESS_REFRESH_POLICY", string)?;
+        from_env(&mut self.concurrency.mirror_workers, "CONCURRENCY_MIRROR_WORKERS", number)?;
+        from_env(&mut self.output.mirror_dir, "OUTPUT_MIRROR_DIR", string)?;
+        from_env(&mut self.output.mirror_sizes, "OUTPUT_MIRROR_SIZES", list)?;
+        from_env(&mut self.output.derivatives_profile, "OUTPUT_DERIVATIVES_PROFILE", string)?;
+        from_env(&mut self.output.export, "OUTPUT_EXPORT", string)?;
+        Ok(())
+    }
+
+    /// check the values, every error is listed at once
+    pub fn validate(&self) -> Result<(), String> {
+        let mut errors = vec![];
+        match self.database.url.as_ref().map(|u| check_postgres_url(u)) {
+            Some(Err(e)) => errors.push(format!("database.url: {}", e)),
+            _ => {},
+        }
+        check_file(&mut errors, "sources.xml", &self.sources.xml);
+        check_file(&mut errors, "sources.components", &self.sources.components);
+        check_file(&mut errors, "sources.search_overrides", &self.sources.search_overrides);
+        check_file(&mut errors, "freshness.refresh_policy", &self.freshness.refresh_policy);
+        check_file(&mut errors, "output.derivatives_profile", &self.output.derivatives_profile);
+        for f in self.sources.provider_fixtures.iter().flat_map(|fs| fs.iter()) {
+            check_file(&mut errors, "sources.provider_fixtures", &Some(f.clone()));
+        }
+        match self.licenses.flickr {
+            Some(ref ls) if ls.is_empty() => errors.push(format!("licenses.flickr: no license")),
+            Some(ref ls) => for l in ls.iter().filter(|l| !HARVESTED_LICENSES.contains(l)) {
+                errors.push(format!("licenses.flickr: {} is not a free license", l));
+            },
+            None => {},
+        }
+        match self.freshness.refresh_days.map(check_refresh_days) {
+            Some(Err(e)) => errors.push(format!("freshness.refresh_days: {}", e)),
+            _ => {},
+        }
+        match self.concurrency.mirror_workers {
+            Some(0) => errors.push(format!("concurrency.mirror_workers: must be positive")),
+            _ => {},
+        }
+        if errors.is_empty() {
+            Ok(())
+        } else {
+            Err(format!("invalid config, {}", errors.join("; ")))
+        }
+    }
+
+    /// the config with the default values of the settings not set
+    pub fn with_defaults(&self) -> Config {
+        let mut c = self.clone();
+        c.sources.commons = c.sources.commons.or(Some(false));
+        c.sources.commons_api = c.sources.commons_api.or(Some(COMMONS_API.to_string()));
+        c.sources.provider_fixtures = c.sources.provider_fixtures.or(Some(vec![]));
+        c.licenses.flickr = c.licenses.flickr.or(Some(HARVESTED_LICENSES.to_vec()));
+        c.freshness.refresh_days = c.freshness.refresh_days.or(Some(DEFAULT_REFRESH_DAYS));
+        c.concurrency.mirror_workers = c.concurrency.mirror_workers.or(Some(DEFAULT_MIRROR_WORKERS));
+        c.output.mirror_sizes = c.output.mirror_sizes
+            .or(Some(DEFAULT_MIRROR_SIZES.iter().map(|s| s.to_string()).collect()));
+        c
+    }
+
+    /// the config with the secrets masked, to be printed
+    pub fn masked(&self) -> Config {
+        let mut c = self.clone();
+        c.database.url = c.database.url.map(|u| mask_url(&*u));
+        c.flickr.key = c.flickr.key.map(|_| MASK.to_string());
+        c
+    }
+
+    pub fn to_toml(&self) -> Result<String, String> {
+        toml::to_string(self).map_err(|e| format!("unable to write config, {}", e))
+    }
+}
@@ -0,0 +1,189 @@
+// Copyright 2017 Jeremy Letang.
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+
+//! Editor commands to pin, reject and order the pictures of a monument.
+
+use clap::{App, Arg, ArgMatches, SubCommand};
+use diesel::Connection;
+use diesel::pg::PgConnection;
+use diesel::result::Error;
+use domain::{self, PictureCuration};
+use transaction_error;
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum Command {
+    List { site: i32 },
+    Pin { picture: String, order: Option<i32>, note: Option<String> },
+    Unpin { picture: String },
+    Reject { picture: String, note: Option<String> },
+    Unreject { picture: String },
+}
+
+pub fn subcommand<'a, 'b>() -> App<'a, 'b> {
+    let picture = Arg::with_name("picture")
+        .help("id of the picture, as shown by list")
+        .required(true);
+    let note = Arg::with_name("note")
+        .long("note")
+        .help("editor note saved with the choice")
+        .takes_value(true);
+
+    SubCommand::with_name("curation")
+        .about("pin, reject or list the pictures of a monument")
+        .subcommand(SubCommand::with_name("list")
+                    .about("list the pictures of a monument with their curation")
+                    .arg(Arg::with_name("site")
+                         .help("id number of the monument in the unesco list")
+                         .required(true)))
+        .subcommand(SubCommand::with_name("pin")
+                    .about("show a picture first")
+                    .arg(picture.clone())
+                    .arg(Arg::with_name("order")
+                         .long("order")
+                         .help("position of the picture among the pinned ones")
+                         .takes_value(true))
+                    .arg(note.clone()))
+        .subcommand(SubCommand::with_name("unpin")
+                    .about("remove the pin of a picture")
+                    .arg(picture.clone()))
+        .subcommand(SubCommand::with_name("reject")
+                    .about("hide a picture and never harvest it again")
+                    .arg(picture.clone())
+                    .arg(note))
+        .subcommand(SubCommand::with_name("unreject")
+                    .about("show a rejected picture again")
+                    .arg(picture))
+}
+
+pub fn from_matches(matches: &ArgMatches) -> Result<Command, String> {
+    fn picture(m: &ArgMatches) -> String {
+        m.value_of("picture").unwrap().into()
+    }
+    fn note(m: &ArgMatches) -> Option<String> {
+        m.value_of("note").map(|s| s.into())
+    }
+
+    match matches.subcommand() {
+        ("list", Some(m)) => m.value_of("site").unwrap().parse()
+            .map(|site| Command::List { site: site })
+            .map_err(|_| format!("site must be a number")),
+        ("pin", Some(m)) => {
+            let order = match m.value_of("order") {
+                Some(o) => Some(o.parse().map_err(|_| format!("order must be a number"))?),
+                None => None,
+            };
+            Ok(Command::Pin { picture: picture(m), order: order, note: note(m) })
+        },
+        ("unpin", Some(m)) => Ok(Command::Unpin { picture: picture(m) }),
+        ("reject", Some(m)) => Ok(Command::Reject { picture: picture(m), note: note(m) }),
+        ("unreject", Some(m)) => Ok(Command::Unreject { picture: picture(m) }),
+        _ => Err(format!("missing curation command, see --help")),
+    }
+}
+
+pub fn run(conn: &PgConnection, cmd: Command) -> Result<(), String> {
+    match cmd {
+        Command::List { site } => list(conn, site),
+        Command::Pin { picture, order, note } => update(conn, &*picture, |c, _| {
+            c.pinned = true;
+            c.sort_order = order;
+            if note.is_some() { c.note = note.clone(); }
+        }),
+        Command::Unpin { picture } => update(conn, &*picture, |c, _| {
+            c.pinned = false;
+            c.sort_order = None;
+        }),
+        Command::Reject { picture, note } => update(conn, &*picture, |c, p| {
+            c.rejected = true;
+            c.pinned = false;
+            if note.is_some() { c.note = note.clone(); }
+            // a deleted, private or relicensed picture keeps its status, so
+            // unreject never serves it again
+            if &*p.status == domain::PICTURE_ACTIVE {
+                p.set_status(domain::PICTURE_REJECTED,
+                             note.clone().unwrap_or("rejected by an editor".into()));
+            }
+        }),
+        Command::Unreject { picture } => update(conn, &*picture, |c, p| {
+            c.rejected = false;
+            if &*p.status == domain::PICTURE_REJECTED {
+                p.set_status(domain::PICTURE_ACTIVE, "restored by an editor");
+            }
+        }),
+    }
+}
+
+fn update<F>(conn: &PgConnection, pid: &str, f: F) -> Result<(), String>
+    where F: Fn(&mut PictureCuration, &mut domain::Picture) {
+    let mut p = match domain::dao::find_picture(conn, pid) {
+        Some(p) => p,
+        None => return Err(format!("unknown picture {}", pid)),
+    };
+    let mut c = domain::dao::find_curation(conn, &*p.provider, &*p.external_id)
+        .unwrap_or(PictureCuration::new(&p));
+    let status = p.status.clone();
+
+    f(&mut c, &mut p);
+    c.touch();
+    // the curation and the status of the picture are saved together
+    conn.transaction::<_, Error, _>(|| {
+        domain::dao::save_curation(conn, &c)?;
+        if p.status != status {
+            domain::dao::update_picture_status(conn, &p)?;
+        }
+        Ok(())
+    }).map_err(|e| format!("picture {} curation not saved, {}", pid, transaction_error(e)))?;
+    info!("picture {} curation saved: {:?}", pid, c);
+    Ok(())
+}
+
+/// the pictures of a monument with their curation, in the order shown by
+/// the clients: pinned first, by sort order, then oldest first
+pub fn ordered(conn: &PgConnection, mid: &str) -> Vec<(domain::Picture, Option<PictureCuration>)> {
+    let curation = domain::dao::list_curation_by_monument_id(conn, mid);
+    let mut pictures = domain::dao::list_pictures_by_monument_id(conn, mid)
+        .into_iter()
+        .map(|p| {
+            let c = curation.iter()
+                .find(|c| c.provider == p.provider && c.external_id == p.external_id)
+                .cloned();
+            (p, c)
+        })
+        .collect::<Vec<_>>();
+    pictures.sort_by_key(|&(ref p, ref c)| {
+        let pinned = c.as_ref().map_or(false, |c| c.pinned);
+        let order = c.as_ref().and_then(|c| c.sort_order).unwrap_or(i32::max_value());
+        (!pinned, order, p.created_at)
+    });
+    pictures
+}
+
+fn list(conn: &PgConnection, site: i32) -> Result<(), String> {
+    let m = match domain::dao::find_monument_by_id_number(conn, site) {
+        Some(m) => m,
+        None => return Err(format!("unknown site {}", site)),
+    };
+    let pictures = ordered(conn, &*m.id);
+
+    println!("{} ({})", m.site.clone().unwrap_or_default(), site);
+    for (p, c) in pictures {
+        let flag = match c {
+            Some(ref c) if c.rejected => "rejected",
+            Some(ref c) if c.pinned => "pinned",
+            _ => "-",
+        };
+        println!("{}\t{}\t{}\t{}:{}\t{}\t{}",
+                 p.id,
+                 flag,
+                 c.as_ref().and_then(|c| c.sort_order).map_or("-".to_string(), |o| o.to_string()),
+                 p.provider,
+                 p.external_id,
+                 p.title.clone().unwrap_or_default(),
+                 c.as_ref().and_then(|c| c.note.clone()).unwrap_or_default());
+    }
+    Ok(())
+}
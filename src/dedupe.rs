@@ -0,0 +1,127 @@
+// Copyright 2017 Jeremy Letang.
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+
+//! Group near-identical pictures using their perceptual hash.
+
+use domain::{Picture, PICTURE_ACTIVE};
+use image::{DynamicImage, FilterType, GenericImage};
+
+/// difference hash of the image: the grayscale image is reduced to 9x8
+/// pixels and each bit tells if a pixel is brighter than its right neighbour
+pub fn dhash(img: &DynamicImage) -> u64 {
+    let small = img.resize_exact(9, 8, FilterType::Triangle).to_luma();
+    let mut hash = 0u64;
+    for y in 0..8 {
+        for x in 0..8 {
+            hash <<= 1;
+            if small.get_pixel(x, y)[0] > small.get_pixel(x + 1, y)[0] {
+                hash |= 1;
+            }
+        }
+    }
+    hash
+}
+
+pub fn distance(a: i64, b: i64) -> u32 {
+    (a ^ b).count_ones()
+}
+
+/// the picture we prefer to keep in a group of duplicates: the largest
+/// original, then the first harvested
+fn rank(p: &Picture) -> (i64, i64) {
+    let area = p.original_width.unwrap_or(0) as i64 * p.original_height.unwrap_or(0) as i64;
+    (area, -p.created_at.timestamp())
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct Group {
+    pub kept: String,
+    pub duplicates: Vec<String>,
+}
+
+/// group the active pictures of one monument whose hashes are at most
+/// `threshold` bits apart, a picture hidden from the clients is never kept
+/// in place of its duplicates. pictures without hash are ignored and groups
+/// of one picture are not returned.
+pub fn groups(pictures: &[Picture], threshold: u32) -> Vec<Group> {
+    let mut hashed = pictures.iter()
+        .filter(|p| p.phash.is_some() && &*p.status == PICTURE_ACTIVE)
+        .collect::<Vec<_>>();
+    // best ranked first so each group is seeded by the picture to keep
+    hashed.sort_by(|a, b| rank(b).cmp(&rank(a)));
+
+    let mut assigned = vec![false; hashed.len()];
+    let mut res = vec![];
+
+    for i in 0..hashed.len() {
+        if assigned[i] {
+            continue
+        }
+        assigned[i] = true;
+        let mut duplicates = vec![];
+        for j in (i + 1)..hashed.len() {
+            if !assigned[j] &&
+                distance(hashed[i].phash.unwrap(), hashed[j].phash.unwrap()) <= threshold {
+                assigned[j] = true;
+                duplicates.push(hashed[j].id.clone());
+            }
+        }
+        if !duplicates.is_empty() {
+            res.push(Group { kept: hashed[i].id.clone(), duplicates: duplicates });
+        }
+    }
+
+    res
+}
+
+/// the picture kept in place of `id`, none for the pictures kept and the
+/// ones in no group, like the inactive ones
+pub fn original<'a>(groups: &'a [Group], id: &str) -> Option<&'a str> {
+    groups.iter()
+        .find(|g| g.duplicates.iter().any(|d| d == id))
+        .map(|g| &*g.kept)
+}
+
+#[cfg(test)]
+mod tests {
+    use domain::{Picture, PICTURE_ACTIVE, PICTURE_DELETED, PICTURE_REJECTED};
+    use super::*;
+
+    fn picture(width: i32, phash: i64, status: &str) -> Picture {
+        let mut p = Picture::new("flickr", "1", "m".into(), "l".into(), "a".into(), "u".into());
+        p.original_width = Some(width);
+        p.original_height = Some(width);
+        p.phash = Some(phash);
+        p.status = status.into();
+        p
+    }
+
+    #[test]
+    fn kept_picture_is_active() {
+        let deleted = picture(4000, 0b1111, PICTURE_DELETED);
+        let rejected = picture(3000, 0b1110, PICTURE_REJECTED);
+        let mut best = picture(2000, 0b1101, PICTURE_ACTIVE);
+        let mut other = picture(1000, 0b0111, PICTURE_ACTIVE);
+        // flagged when the deleted picture was still active
+        best.duplicate_of = Some(deleted.id.clone());
+        other.duplicate_of = Some(deleted.id.clone());
+        let pictures = vec![deleted.clone(), rejected.clone(), best.clone(), other.clone()];
+
+        let groups = groups(&pictures, 2);
+        assert_eq!(groups, vec![Group { kept: best.id.clone(), duplicates: vec![other.id.clone()] }]);
+        assert_eq!(original(&groups, &*deleted.id), None);
+        assert_eq!(original(&groups, &*rejected.id), None);
+        assert_eq!(original(&groups, &*best.id), None);
+        assert_eq!(original(&groups, &*other.id), Some(&*best.id));
+    }
+
+    #[test]
+    fn inactive_pictures_are_not_grouped() {
+        let pictures = vec![picture(2000, 0, PICTURE_ACTIVE), picture(1000, 0, PICTURE_DELETED)];
+        assert!(groups(&pictures, 0).is_empty());
+    }
+}
@@ -0,0 +1,227 @@
+// Copyright 2017 Jeremy Letang.
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+
+//! Generate resized copies and placeholders of the mirrored pictures.
+//!
+//! The webp copies are lossless, the quality of their derivatives is
+//! ignored.
+
+use image::{self, DynamicImage, FilterType, GenericImage};
+use image::jpeg::JPEGEncoder;
+use image::png::PNGEncoder;
+use serde_json;
+use std::f32::consts::PI;
+use std::fs::File;
+use std::io::Read;
+use webp;
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub enum Format {
+    #[serde(rename = "jpeg")]
+    Jpeg,
+    #[serde(rename = "png")]
+    Png,
+    #[serde(rename = "webp")]
+    WebP,
+}
+
+impl Format {
+    pub fn mime_type(&self) -> &'static str {
+        match *self {
+            Format::Jpeg => "image/jpeg",
+            Format::Png => "image/png",
+            Format::WebP => "image/webp",
+        }
+    }
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct Derivative {
+    pub name: String,
+    pub width: u32,
+    pub format: Format,
+    /// ignored for webp
+    #[serde(default = "default_quality")]
+    pub quality: u8,
+}
+
+fn default_quality() -> u8 { 80 }
+
+fn default_true() -> bool { true }
+
+/// the list of derivatives to generate for each picture
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct Profile {
+    pub derivatives: Vec<Derivative>,
+    #[serde(default = "default_true")]
+    pub placeholder: bool,
+}
+
+impl Default for Profile {
+    fn default() -> Profile {
+        Profile {
+            derivatives: vec![
+                Derivative { name: "thumb".into(), width: 320, format: Format::Jpeg, quality: 80 },
+                Derivative { name: "thumb_webp".into(), width: 320, format: Format::WebP, quality: 80 },
+                Derivative { name: "medium".into(), width: 1024, format: Format::Jpeg, quality: 80 },
+                Derivative { name: "medium_webp".into(), width: 1024, format: Format::WebP, quality: 80 },
+            ],
+            placeholder: true,
+        }
+    }
+}
+
+impl Profile {
+    pub fn from_file(file: &str) -> Result<Profile, String> {
+        info!("reading derivatives profile: '{}'", file);
+        let mut buf = String::new();
+        match File::open(file).and_then(|mut f| f.read_to_string(&mut buf)) {
+            Ok(_) => serde_json::from_str::<Profile>(&*buf)
+                .map_err(|e| format!("invalid derivatives profile, {}", e)),
+            Err(e) => Err(format!("unable to read derivatives profile, {}", e)),
+        }
+    }
+}
+
+pub struct Rendered {
+    pub buf: Vec<u8>,
+    pub width: u32,
+    pub height: u32,
+}
+
+pub fn load(buf: &[u8]) -> Result<DynamicImage, String> {
+    image::load_from_memory(buf).map_err(|e| format!("unable to decode image, {}", e))
+}
+
+/// resize `img` to the width of the derivative, keeping the ratio, and
+/// encode it. images smaller than the derivative are never upscaled.
+pub fn render(img: &DynamicImage, d: &Derivative) -> Result<Rendered, String> {
+    let (w, h) = img.dimensions();
+    let resized = if w > d.width {
+        let nh = ((h as f32) * (d.width as f32) / (w as f32)).round().max(1.) as u32;
+        img.resize_exact(d.width, nh, FilterType::Lanczos3)
+    } else {
+        img.clone()
+    };
+    let rgb = resized.to_rgb();
+    let (width, height) = rgb.dimensions();
+    let mut buf = vec![];
+
+    let res = match d.format {
+        Format::Jpeg => JPEGEncoder::new_with_quality(&mut buf, d.quality)
+            .encode(&rgb, width, height, image::RGB(8))
+            .map_err(|e| format!("{}", e)),
+        Format::Png => PNGEncoder::new(&mut buf)
+            .encode(&rgb, width, height, image::RGB(8))
+            .map_err(|e| format!("{}", e)),
+        // lossless, the quality is ignored
+        Format::WebP => webp::encode(&rgb, width, height).map(|webp| buf = webp),
+    };
+
+    match res {
+        Ok(_) => Ok(Rendered { buf: buf, width: width, height: height }),
+        Err(e) => Err(format!("unable to encode derivative {}, {}", d.name, e)),
+    }
+}
+
+/// average color of the image as an html hex color
+pub fn dominant_color(img: &DynamicImage) -> String {
+    let small = img.resize(64, 64, FilterType::Triangle).to_rgb();
+    let (mut r, mut g, mut b) = (0u64, 0u64, 0u64);
+    for p in small.pixels() {
+        r += p[0] as u64;
+        g += p[1] as u64;
+        b += p[2] as u64;
+    }
+    let n = (small.width() * small.height()).max(1) as u64;
+    format!("#{:02x}{:02x}{:02x}", r / n, g / n, b / n)
+}
+
+const BASE83: &'static [u8] =
+    b"0123456789ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz#$%*+,-.:;=?@[]^_{|}~";
+
+/// blurhash (https://blurha.sh) of the image with 4x3 components
+pub fn blurhash(img: &DynamicImage) -> String {
+    let (cx, cy) = (4usize, 3usize);
+    let small = img.resize(32, 32, FilterType::Triangle).to_rgb();
+    let (w, h) = small.dimensions();
+
+    let mut factors = vec![];
+    for j in 0..cy {
+        for i in 0..cx {
+            let norm = if i == 0 && j == 0 { 1. } else { 2. };
+            let mut f = [0f32; 3];
+            for y in 0..h {
+                for x in 0..w {
+                    let basis = (PI * i as f32 * x as f32 / w as f32).cos() *
+                        (PI * j as f32 * y as f32 / h as f32).cos();
+                    let p = small.get_pixel(x, y);
+                    for c in 0..3 {
+                        f[c] += basis * srgb_to_linear(p[c]);
+                    }
+                }
+            }
+            let scale = norm / (w * h) as f32;
+            factors.push([f[0] * scale, f[1] * scale, f[2] * scale]);
+        }
+    }
+
+    let mut hash = String::new();
+    encode83((cx - 1 + (cy - 1) * 9) as u32, 1, &mut hash);
+
+    let ac = &factors[1..];
+    let max_value = if ac.len() > 0 {
+        let actual = ac.iter()
+            .flat_map(|f| f.iter())
+            .fold(0f32, |m, v| m.max(v.abs()));
+        let quantised = (actual * 166. - 0.5).floor().max(0.).min(82.) as u32;
+        encode83(quantised, 1, &mut hash);
+        (quantised + 1) as f32 / 166.
+    } else {
+        encode83(0, 1, &mut hash);
+        1.
+    };
+
+    let dc = &factors[0];
+    let dc_value = (linear_to_srgb(dc[0]) << 16) + (linear_to_srgb(dc[1]) << 8) + linear_to_srgb(dc[2]);
+    encode83(dc_value, 4, &mut hash);
+
+    for f in ac {
+        let q = |v: f32| {
+            (sign_pow(v / max_value, 0.5) * 9. + 9.5).floor().max(0.).min(18.) as u32
+        };
+        encode83(q(f[0]) * 19 * 19 + q(f[1]) * 19 + q(f[2]), 2, &mut hash);
+    }
+
+    hash
+}
+
+fn encode83(value: u32, length: u32, out: &mut String) {
+    for i in 1..(length + 1) {
+        let digit = (value / 83u32.pow(length - i)) % 83;
+        out.push(BASE83[digit as usize] as char);
+    }
+}
+
+fn srgb_to_linear(v: u8) -> f32 {
+    let v = v as f32 / 255.;
+    if v <= 0.04045 { v / 12.92 } else { ((v + 0.055) / 1.055).powf(2.4) }
+}
+
+fn linear_to_srgb(v: f32) -> u32 {
+    let v = v.max(0.).min(1.);
+    if v <= 0.0031308 {
+        (v * 12.92 * 255. + 0.5) as u32
+    } else {
+        ((1.055 * v.powf(1. / 2.4) - 0.055) * 255. + 0.5) as u32
+    }
+}
+
+fn sign_pow(v: f32, exp: f32) -> f32 {
+    let r = v.abs().powf(exp);
+    if v < 0. { -r } else { r }
+}
@@ -0,0 +1,251 @@
+// Copyright 2017 Jeremy Letang.
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+
+//! Credit lines for the pictures, following the attribution rules of the
+//! creative commons licenses (title, author, license and modifications).
+
+use providers::commons::COMMONS;
+use providers::flickr::FLICKR;
+use super::{License, Picture};
+
+/// how a license must be credited
+#[derive(Clone, Debug, PartialEq)]
+enum Kind {
+    /// a creative commons license requiring attribution, with its short name
+    Cc(String),
+    /// dedicated to the public domain, attribution is only a courtesy
+    PublicDomain(String),
+    /// no creative commons license, only credit the author
+    Other,
+}
+
+fn kind(l: &License) -> Kind {
+    // flickr license ids, see flickr.photos.licenses.getInfo
+    match l.flickr_id {
+        Some(1) => Kind::Cc("CC BY-NC-SA 2.0".into()),
+        Some(2) => Kind::Cc("CC BY-NC 2.0".into()),
+        Some(3) => Kind::Cc("CC BY-NC-ND 2.0".into()),
+        Some(4) => Kind::Cc("CC BY 2.0".into()),
+        Some(5) => Kind::Cc("CC BY-SA 2.0".into()),
+        Some(6) => Kind::Cc("CC BY-ND 2.0".into()),
+        Some(7) => Kind::PublicDomain("No known copyright restrictions".into()),
+        Some(8) => Kind::PublicDomain("United States Government Work".into()),
+        Some(9) => Kind::PublicDomain("CC0 1.0".into()),
+        Some(10) => Kind::PublicDomain("Public Domain Mark 1.0".into()),
+        Some(_) => Kind::Other,
+        // other providers give the short name of the license, like
+        // "CC BY-SA 4.0" or "Public domain" on commons
+        None => {
+            let lower = l.name.to_lowercase();
+            if lower.starts_with("cc0") || lower.starts_with("public domain") || lower == "pd" {
+                Kind::PublicDomain(l.name.clone())
+            } else if lower.starts_with("cc by") || lower.starts_with("cc-by") {
+                Kind::Cc(l.name.clone())
+            } else {
+                Kind::Other
+            }
+        },
+    }
+}
+
+/// page of the author of a picture on its provider
+fn profile_url(p: &Picture) -> Option<String> {
+    match &*p.provider {
+        FLICKR => p.owner_nsid.as_ref()
+            .map(|nsid| format!("https://www.flickr.com/people/{}/", nsid)),
+        COMMONS => p.owner_nsid.as_ref()
+            .map(|user| format!("https://commons.wikimedia.org/wiki/User:{}", user.replace(" ", "_"))),
+        _ => None,
+    }
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct Attribution {
+    pub text: String,
+    pub html: String,
+}
+
+impl Attribution {
+    /// `modified` must be true if the picture is displayed through one of
+    /// its derivatives rather than the original file
+    pub fn new(p: &Picture, l: &License, modified: bool) -> Attribution {
+        let title = p.title.clone().unwrap_or("Untitled".into());
+        let author = p.owner_realname.clone().unwrap_or(p.author.clone());
+        let profile = profile_url(p);
+
+        let html_title = link(p.page_url.as_ref(), &*title);
+        let html_author = link(profile.as_ref(), &*author);
+
+        let (mut text, mut html) = match kind(l) {
+            Kind::Cc(short) => (
+                format!("\"{}\" by {} is licensed under {}", title, author,
+                        with_url(&*short, l.url.as_ref())),
+                format!("{} by {} is licensed under {}", html_title, html_author,
+                        link(l.url.as_ref(), &*short)),
+            ),
+            Kind::PublicDomain(short) => (
+                format!("\"{}\" by {} is marked with {}", title, author,
+                        with_url(&*short, l.url.as_ref())),
+                format!("{} by {} is marked with {}", html_title, html_author,
+                        link(l.url.as_ref(), &*short)),
+            ),
+            Kind::Other => (
+                format!("\"{}\" by {} ({})", title, author, l.name),
+                format!("{} by {} ({})", html_title, html_author, escape(&*l.name)),
+            ),
+        };
+        text.push('.');
+        html.push('.');
+
+        if modified {
+            text.push_str(" Resized and converted from the original.");
+            html.push_str(" Resized and converted from the original.");
+        }
+
+        Attribution { text: text, html: html }
+    }
+}
+
+fn with_url(s: &str, url: Option<&String>) -> String {
+    match url {
+        Some(u) => format!("{} ({})", s, u),
+        None => s.to_string(),
+    }
+}
+
+fn link(url: Option<&String>, s: &str) -> String {
+    match url {
+        Some(u) => format!("<a href=\"{}\">{}</a>", escape(u), escape(s)),
+        None => escape(s),
+    }
+}
+
+fn escape(s: &str) -> String {
+    let mut res = String::with_capacity(s.len());
+    for c in s.chars() {
+        match c {
+            '&' => res.push_str("&amp;"),
+            '<' => res.push_str("&lt;"),
+            '>' => res.push_str("&gt;"),
+            '"' => res.push_str("&quot;"),
+            '\'' => res.push_str("&#39;"),
+            c => res.push(c),
+        }
+    }
+    res
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use providers::ProviderLicense;
+
+    fn license(flickr_id: Option<i32>, name: &str, url: Option<&str>) -> License {
+        let pl = ProviderLicense {
+            flickr_id: flickr_id,
+            name: name.to_string(),
+            url: url.map(|u| u.to_string()),
+        };
+        License::new(if flickr_id.is_some() { FLICKR } else { COMMONS }, &pl)
+    }
+
+    fn picture() -> Picture {
+        let mut p = Picture::new(FLICKR, "42", "m".into(), "l".into(), "jdoe".into(),
+                                 "https://farm.staticflickr.com/42.jpg".into());
+        p.title = Some("Old town".into());
+        p.owner_nsid = Some("12@N01".into());
+        p.owner_realname = Some("John Doe".into());
+        p.page_url = Some("https://www.flickr.com/photos/jdoe/42/".into());
+        p
+    }
+
+    #[test]
+    fn kind_of_flickr_licenses() {
+        let cases = vec![
+            (1, Kind::Cc("CC BY-NC-SA 2.0".into())),
+            (2, Kind::Cc("CC BY-NC 2.0".into())),
+            (3, Kind::Cc("CC BY-NC-ND 2.0".into())),
+            (4, Kind::Cc("CC BY 2.0".into())),
+            (5, Kind::Cc("CC BY-SA 2.0".into())),
+            (6, Kind::Cc("CC BY-ND 2.0".into())),
+            (7, Kind::PublicDomain("No known copyright restrictions".into())),
+            (8, Kind::PublicDomain("United States Government Work".into())),
+            (9, Kind::PublicDomain("CC0 1.0".into())),
+            (10, Kind::PublicDomain("Public Domain Mark 1.0".into())),
+            (0, Kind::Other),
+            (11, Kind::Other),
+        ];
+        for (id, expected) in cases {
+            assert_eq!(kind(&license(Some(id), "flickr name", None)), expected, "license {}", id);
+        }
+    }
+
+    #[test]
+    fn kind_of_named_licenses() {
+        let cases = vec![
+            ("CC BY-SA 4.0", Kind::Cc("CC BY-SA 4.0".into())),
+            ("cc-by-3.0", Kind::Cc("cc-by-3.0".into())),
+            ("CC0", Kind::PublicDomain("CC0".into())),
+            ("Public domain", Kind::PublicDomain("Public domain".into())),
+            ("PD", Kind::PublicDomain("PD".into())),
+            ("GFDL", Kind::Other),
+        ];
+        for (name, expected) in cases {
+            assert_eq!(kind(&license(None, name, None)), expected, "license {}", name);
+        }
+    }
+
+    #[test]
+    fn cc_attribution() {
+        let l = license(Some(4), "Attribution License", Some("https://creativecommons.org/licenses/by/2.0/"));
+        let a = Attribution::new(&picture(), &l, false);
+        assert_eq!(a.text, "\"Old town\" by John Doe is licensed under \
+                            CC BY 2.0 (https://creativecommons.org/licenses/by/2.0/).");
+        assert_eq!(a.html, "<a href=\"https://www.flickr.com/photos/jdoe/42/\">Old town</a> by \
+                            <a href=\"https://www.flickr.com/people/12@N01/\">John Doe</a> is licensed under \
+                            <a href=\"https://creativecommons.org/licenses/by/2.0/\">CC BY 2.0</a>.");
+    }
+
+    #[test]
+    fn public_domain_attribution() {
+        let mut p = picture();
+        p.title = None;
+        p.owner_realname = None;
+        p.page_url = None;
+        p.owner_nsid = None;
+        let a = Attribution::new(&p, &license(Some(9), "CC0", None), false);
+        assert_eq!(a.text, "\"Untitled\" by jdoe is marked with CC0 1.0.");
+        assert_eq!(a.html, "Untitled by jdoe is marked with CC0 1.0.");
+    }
+
+    #[test]
+    fn other_attribution() {
+        let a = Attribution::new(&picture(), &license(Some(0), "All Rights Reserved", None), false);
+        assert_eq!(a.text, "\"Old town\" by John Doe (All Rights Reserved).");
+    }
+
+    #[test]
+    fn modified_attribution() {
+        let a = Attribution::new(&picture(), &license(Some(5), "", None), true);
+        assert!(a.text.ends_with("CC BY-SA 2.0. Resized and converted from the original."));
+        assert!(a.html.ends_with("CC BY-SA 2.0. Resized and converted from the original."));
+    }
+
+    #[test]
+    fn html_is_escaped() {
+        let mut p = picture();
+        p.title = Some("<b>Tom & Jerry's</b>".into());
+        p.owner_realname = Some("\"J\"".into());
+        p.page_url = Some("https://example.com/?a=1&b=\"2\"".into());
+        let a = Attribution::new(&p, &license(Some(0), "<i>custom</i>", None), false);
+        assert_eq!(a.text, "\"<b>Tom & Jerry's</b>\" by \"J\" (<i>custom</i>).");
+        assert_eq!(a.html, "<a href=\"https://example.com/?a=1&amp;b=&quot;2&quot;\">\
+                            &lt;b&gt;Tom &amp; Jerry&#39;s&lt;/b&gt;</a> by \
+                            <a href=\"https://www.flickr.com/people/12@N01/\">&quot;J&quot;</a> \
+                            (&lt;i&gt;custom&lt;/i&gt;).");
+    }
+}
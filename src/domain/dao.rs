@@ -7,25 +7,140 @@
 
 use diesel;
 use diesel::pg::PgConnection;
-use super::{Picture, Monument, License, LastUpdate};
+use providers::ProviderLicense;
+use std::collections::HashSet;
+use super::{Picture, PictureSize, PictureDerivative, PictureCuration, SearchOverride, Monument,
+            MonumentComponent, License, LastUpdate, TaskRun};
 
-pub fn update_monument(conn: &PgConnection, m: &Monument) {
-    use diesel::{ExecuteDsl, FilterDsl, ExpressionMethods};
+// the new texts of the monuments, a temporary table of the transaction
+// updating them
+table! {
+    monument_texts (id) {
+        id -> VarChar,
+        criteria_txt -> Nullable<Text>,
+        danger -> Nullable<Text>,
+        historical_description -> Nullable<Text>,
+        justification -> Nullable<Text>,
+        location -> Nullable<Text>,
+        long_description -> Nullable<Text>,
+        secondary_dates -> Nullable<Text>,
+        short_description -> Nullable<Text>,
+        site -> Nullable<Text>,
+        states -> Nullable<Text>,
+        short_description_html -> Nullable<Text>,
+        long_description_html -> Nullable<Text>,
+        justification_html -> Nullable<Text>,
+        historical_description_html -> Nullable<Text>,
+        short_description_markdown -> Nullable<Text>,
+        long_description_markdown -> Nullable<Text>,
+        justification_markdown -> Nullable<Text>,
+        historical_description_markdown -> Nullable<Text>,
+    }
+}
 
-    use domain::schema::monuments::dsl::{monuments, id, site, short_description, long_description};
-    let _ = diesel::update(monuments.filter(id.eq(&m.id)))
-        .set((
-            site.eq(&m.site),
-            long_description.eq(&m.long_description),
-            short_description.eq(&m.short_description)
-        )).execute(conn);
+#[derive(Insertable)]
+#[table_name="monument_texts"]
+struct MonumentTexts {
+    id: String,
+    criteria_txt: Option<String>,
+    danger: Option<String>,
+    historical_description: Option<String>,
+    justification: Option<String>,
+    location: Option<String>,
+    long_description: Option<String>,
+    secondary_dates: Option<String>,
+    short_description: Option<String>,
+    site: Option<String>,
+    states: Option<String>,
+    short_description_html: Option<String>,
+    long_description_html: Option<String>,
+    justification_html: Option<String>,
+    historical_description_html: Option<String>,
+    short_description_markdown: Option<String>,
+    long_description_markdown: Option<String>,
+    justification_markdown: Option<String>,
+    historical_description_markdown: Option<String>,
+}
+
+impl<'a> From<&'a Monument> for MonumentTexts {
+    fn from(m: &Monument) -> MonumentTexts {
+        MonumentTexts {
+            id: m.id.clone(),
+            criteria_txt: m.criteria_txt.clone(),
+            danger: m.danger.clone(),
+            historical_description: m.historical_description.clone(),
+            justification: m.justification.clone(),
+            location: m.location.clone(),
+            long_description: m.long_description.clone(),
+            secondary_dates: m.secondary_dates.clone(),
+            short_description: m.short_description.clone(),
+            site: m.site.clone(),
+            states: m.states.clone(),
+            short_description_html: m.short_description_html.clone(),
+            long_description_html: m.long_description_html.clone(),
+            justification_html: m.justification_html.clone(),
+            historical_description_html: m.historical_description_html.clone(),
+            short_description_markdown: m.short_description_markdown.clone(),
+            long_description_markdown: m.long_description_markdown.clone(),
+            justification_markdown: m.justification_markdown.clone(),
+            historical_description_markdown: m.historical_description_markdown.clone(),
+        }
+    }
+}
+
+/// rows of texts by insert, 19 parameters each, below the 65535 parameters
+/// postgres accepts
+const MONUMENT_TEXTS_BATCH_SIZE: usize = 2000;
+
+/// the columns of `monument_texts` copied to the monuments
+const MONUMENT_TEXTS: [&'static str; 18] = [
+    "criteria_txt", "danger", "historical_description", "justification", "location",
+    "long_description", "secondary_dates", "short_description", "site", "states",
+    "short_description_html", "long_description_html", "justification_html",
+    "historical_description_html", "short_description_markdown", "long_description_markdown",
+    "justification_markdown", "historical_description_markdown",
+];
+
+/// save the texts of the monuments, all of them or none. the texts are
+/// bound in a temporary table, then copied by a single update
+pub fn update_monuments(conn: &PgConnection, ms: &[Monument]) -> Result<usize, diesel::result::Error> {
+    use diesel::{Connection, ExecuteDsl};
+    use diesel::connection::SimpleConnection;
+    use diesel::result::TransactionError;
+
+    if ms.is_empty() {
+        return Ok(0)
+    }
+    let texts = ms.iter().map(MonumentTexts::from).collect::<Vec<_>>();
+    let create = format!("CREATE TEMPORARY TABLE monument_texts (id VARCHAR PRIMARY KEY, {}) ON COMMIT DROP",
+                         MONUMENT_TEXTS.iter().map(|c| format!("{} TEXT", c)).collect::<Vec<_>>().join(", "));
+    let update = format!("UPDATE monuments SET {} FROM monument_texts t WHERE monuments.id = t.id",
+                         MONUMENT_TEXTS.iter().map(|c| format!("{} = t.{}", c, c)).collect::<Vec<_>>().join(", "));
+
+    let res = conn.transaction::<_, diesel::result::Error, _>(|| {
+        conn.batch_execute(&*create)?;
+        for batch in texts.chunks(MONUMENT_TEXTS_BATCH_SIZE) {
+            diesel::insert(batch).into(monument_texts::table).execute(conn)?;
+        }
+        let updated = conn.execute(&*update)?;
+        // dropped now in case the caller's transaction updates again
+        conn.batch_execute("DROP TABLE monument_texts")?;
+        Ok(updated)
+    });
+    res.map_err(|e| match e {
+        TransactionError::CouldntCreateTransaction(e) => e,
+        TransactionError::UserReturnedError(e) => e,
+    })
 }
 
 pub fn update_last_update(conn: &PgConnection, u: &LastUpdate) {
     use diesel::{ExecuteDsl, FilterDsl, ExpressionMethods};
-    use domain::schema::last_updates::dsl::{last_updates, id, updated_at};
+    use domain::schema::last_updates::dsl::{last_updates, id, updated_at, empty_runs};
     let _ = diesel::update(last_updates.filter(id.eq(&u.id)))
-        .set(updated_at.eq(u.updated_at)).execute(conn);
+        .set((
+            updated_at.eq(u.updated_at),
+            empty_runs.eq(u.empty_runs)
+        )).execute(conn);
 }
 
 pub fn last_update_by_monument_id(conn: &PgConnection, mid: &str) -> Option<LastUpdate> {
@@ -34,24 +149,468 @@ pub fn last_update_by_monument_id(conn: &PgConnection, mid: &str) -> Option<Last
     last_updates.filter(monument_id.eq(mid)).first::<LastUpdate>(conn).ok()
 }
 
-pub fn picture_exists(conn: &PgConnection, fid: &str) -> bool {
+/// whether the last updates have their number of empty runs, the schemas
+/// before the backoff do not
+pub fn last_updates_have_empty_runs(conn: &PgConnection) -> bool {
+    use diesel::LoadDsl;
+    use diesel::expression::dsl::sql;
+    use diesel::types::Bool;
+
+    sql::<Bool>("SELECT EXISTS (SELECT 1 FROM information_schema.columns \
+                 WHERE table_name = 'last_updates' AND column_name = 'empty_runs')")
+        .get_result::<bool>(conn)
+        .unwrap()
+}
+
+/// every last update, without empty run when `with_runs` is not set, the
+/// other columns are in every version of the table
+pub fn list_last_updates(conn: &PgConnection, with_runs: bool) -> Vec<LastUpdate> {
+    use diesel::LoadDsl;
+    use diesel::expression::dsl::sql;
+    use diesel::types::{Integer, Timestamp, VarChar};
+
+    let query = if with_runs {
+        "SELECT id, monument_id, created_at, updated_at, empty_runs FROM last_updates"
+    } else {
+        "SELECT id, monument_id, created_at, updated_at, 0 FROM last_updates"
+    };
+    sql::<(VarChar, VarChar, Timestamp, Timestamp, Integer)>(query)
+        .load::<LastUpdate>(conn)
+        .unwrap()
+}
+
+/// keep `u` in place of the last updates `merged`, with its dates and its
+/// empty runs when `with_runs` is set
+pub fn merge_last_updates(conn: &PgConnection, u: &LastUpdate, merged: &[String], with_runs: bool)
+                          -> Result<(), diesel::result::Error> {
+    use diesel::{ExecuteDsl, FilterDsl, ExpressionMethods};
+    use domain::schema::last_updates::dsl::{last_updates, id, created_at, updated_at, empty_runs};
+
+    let kept = last_updates.filter(id.eq(&u.id));
+    if with_runs {
+        diesel::update(kept)
+            .set((created_at.eq(u.created_at), updated_at.eq(u.updated_at), empty_runs.eq(u.empty_runs)))
+            .execute(conn)?;
+    } else {
+        diesel::update(kept)
+            .set((created_at.eq(u.created_at), updated_at.eq(u.updated_at)))
+            .execute(conn)?;
+    }
+    diesel::delete(last_updates.filter(id.eq_any(merged))).execute(conn)?;
+    Ok(())
+}
+
+pub fn list_task_runs(conn: &PgConnection, t: &str) -> Vec<TaskRun> {
     use diesel::{LoadDsl, FilterDsl, ExpressionMethods};
-    use domain::schema::pictures::dsl::{pictures, flickr_id};
+    use domain::schema::task_runs::dsl::{task_runs, task};
+    task_runs.filter(task.eq(t)).load::<TaskRun>(conn).unwrap()
+}
 
-    match pictures.filter(flickr_id.eq(&fid)).load::<Picture>(conn) {
+pub fn save_task_run(conn: &PgConnection, r: &TaskRun) -> Result<(), diesel::result::Error> {
+    use diesel::{ExecuteDsl, FilterDsl, ExpressionMethods};
+    use domain::schema::task_runs::dsl::*;
+
+    let updated = diesel::update(task_runs.filter(id.eq(&r.id)))
+        .set((
+            last_success_at.eq(r.last_success_at),
+            last_attempt_at.eq(r.last_attempt_at),
+            last_error.eq(&r.last_error),
+            empty_runs.eq(r.empty_runs),
+            updated_at.eq(r.updated_at)
+        )).execute(conn)?;
+    if updated == 0 {
+        diesel::insert(r).into(task_runs).execute(conn)?;
+    }
+    Ok(())
+}
+
+pub fn picture_exists(conn: &PgConnection, pname: &str, eid: &str) -> bool {
+    use diesel::{LoadDsl, FilterDsl, ExpressionMethods};
+    use domain::schema::pictures::dsl::{pictures, provider, external_id};
+
+    match pictures.filter(provider.eq(pname))
+        .filter(external_id.eq(eid))
+        .load::<Picture>(conn) {
         Ok(v) => v.len() != 0,
         Err(_) => false,
     }
 }
 
+pub fn update_picture_metadata(conn: &PgConnection, p: &Picture) {
+    use diesel::{ExecuteDsl, FilterDsl, ExpressionMethods};
+    use domain::schema::pictures::dsl::*;
+    let _ = diesel::update(pictures.filter(id.eq(&p.id)))
+        .set((
+            author.eq(&p.author),
+            title.eq(&p.title),
+            description.eq(&p.description),
+            date_taken.eq(&p.date_taken),
+            owner_nsid.eq(&p.owner_nsid),
+            owner_realname.eq(&p.owner_realname),
+            page_url.eq(&p.page_url),
+            latitude.eq(&p.latitude),
+            longitude.eq(&p.longitude),
+            tags.eq(&p.tags),
+            original_width.eq(&p.original_width),
+            original_height.eq(&p.original_height)
+        )).execute(conn);
+}
+
+/// active flickr pictures saved before the metadata columns were added
+pub fn list_pictures_without_metadata(conn: &PgConnection) -> Vec<Picture> {
+    use diesel::{LoadDsl, FilterDsl, ExpressionMethods};
+    use domain::schema::pictures::dsl::{pictures, owner_nsid, provider, status};
+    use domain::PICTURE_ACTIVE;
+    use providers::flickr::FLICKR;
+    pictures.filter(provider.eq(FLICKR))
+        .filter(status.eq(PICTURE_ACTIVE))
+        .filter(owner_nsid.is_null())
+        .load::<Picture>(conn).unwrap()
+}
+
+pub fn list_picture_sizes(conn: &PgConnection, pid: &str) -> Vec<PictureSize> {
+    use diesel::{LoadDsl, FilterDsl, ExpressionMethods};
+    use domain::schema::picture_sizes::dsl::{picture_sizes, picture_id};
+    picture_sizes.filter(picture_id.eq(pid)).load::<PictureSize>(conn).unwrap()
+}
+
+pub fn list_picture_sizes_by_labels(conn: &PgConnection, labels: &[String]) -> Vec<PictureSize> {
+    use diesel::{LoadDsl, FilterDsl, ExpressionMethods};
+    use domain::schema::picture_sizes::dsl::{picture_sizes, label};
+    picture_sizes.filter(label.eq_any(labels)).load::<PictureSize>(conn).unwrap()
+}
+
+pub fn update_picture_size_file(conn: &PgConnection, ps: &PictureSize) {
+    use diesel::{ExecuteDsl, FilterDsl, ExpressionMethods};
+    use domain::schema::picture_sizes::dsl::{picture_sizes, id, sha256, file_size, mime_type, mirrored_at};
+    let _ = diesel::update(picture_sizes.filter(id.eq(&ps.id)))
+        .set((
+            sha256.eq(&ps.sha256),
+            file_size.eq(&ps.file_size),
+            mime_type.eq(&ps.mime_type),
+            mirrored_at.eq(&ps.mirrored_at)
+        )).execute(conn);
+}
+
+/// renditions of a picture already downloaded in the mirror
+pub fn list_mirrored_picture_sizes(conn: &PgConnection, pid: &str) -> Vec<PictureSize> {
+    use diesel::{LoadDsl, FilterDsl, ExpressionMethods};
+    use domain::schema::picture_sizes::dsl::{picture_sizes, picture_id, sha256};
+    picture_sizes.filter(picture_id.eq(pid))
+        .filter(sha256.is_not_null())
+        .load::<PictureSize>(conn).unwrap()
+}
+
+pub fn derivative_exists(conn: &PgConnection, pid: &str, dname: &str) -> bool {
+    use diesel::{LoadDsl, FilterDsl, ExpressionMethods};
+    use domain::schema::picture_derivatives::dsl::{picture_derivatives, picture_id, name};
+
+    match picture_derivatives.filter(picture_id.eq(pid))
+        .filter(name.eq(dname))
+        .load::<PictureDerivative>(conn) {
+        Ok(v) => v.len() != 0,
+        Err(_) => false,
+    }
+}
+
+/// ids of the pictures having at least one derivative
+pub fn list_picture_ids_with_derivatives(conn: &PgConnection) -> HashSet<String> {
+    use diesel::{LoadDsl, SelectDsl};
+    use domain::schema::picture_derivatives::dsl::{picture_derivatives, picture_id};
+
+    match picture_derivatives.select(picture_id).load::<String>(conn) {
+        Ok(v) => v.into_iter().collect(),
+        Err(_) => HashSet::new(),
+    }
+}
+
+pub fn update_picture_attribution(conn: &PgConnection, p: &Picture) {
+    use diesel::{ExecuteDsl, FilterDsl, ExpressionMethods};
+    use domain::schema::pictures::dsl::{pictures, id, attribution_text, attribution_html};
+    let _ = diesel::update(pictures.filter(id.eq(&p.id)))
+        .set((
+            attribution_text.eq(&p.attribution_text),
+            attribution_html.eq(&p.attribution_html)
+        )).execute(conn);
+}
+
+pub fn update_picture_placeholder(conn: &PgConnection, p: &Picture) {
+    use diesel::{ExecuteDsl, FilterDsl, ExpressionMethods};
+    use domain::schema::pictures::dsl::{pictures, id, blurhash, dominant_color};
+    let _ = diesel::update(pictures.filter(id.eq(&p.id)))
+        .set((
+            blurhash.eq(&p.blurhash),
+            dominant_color.eq(&p.dominant_color)
+        )).execute(conn);
+}
+
+pub fn update_picture_phash(conn: &PgConnection, p: &Picture) {
+    use diesel::{ExecuteDsl, FilterDsl, ExpressionMethods};
+    use domain::schema::pictures::dsl::{pictures, id, phash};
+    let _ = diesel::update(pictures.filter(id.eq(&p.id)))
+        .set(phash.eq(&p.phash)).execute(conn);
+}
+
+pub fn update_picture_duplicate_of(conn: &PgConnection, pid: &str, original: Option<&str>) {
+    use diesel::{ExecuteDsl, FilterDsl, ExpressionMethods};
+    use domain::schema::pictures::dsl::{pictures, id, duplicate_of};
+    let _ = diesel::update(pictures.filter(id.eq(pid)))
+        .set(duplicate_of.eq(original)).execute(conn);
+}
+
+pub fn update_picture_status(conn: &PgConnection, p: &Picture) -> Result<usize, diesel::result::Error> {
+    use diesel::{ExecuteDsl, FilterDsl, ExpressionMethods};
+    use domain::schema::pictures::dsl::*;
+    diesel::update(pictures.filter(id.eq(&p.id)))
+        .set((
+            license_id.eq(&p.license_id),
+            status.eq(&p.status),
+            status_reason.eq(&p.status_reason),
+            status_changed_at.eq(&p.status_changed_at),
+            verified_at.eq(&p.verified_at)
+        )).execute(conn)
+}
+
+/// delete the renditions and derivatives of a picture, the row is kept
+/// with its status, reason and timestamp as a record of the purge
+pub fn purge_picture(conn: &PgConnection, p: &Picture) -> Result<(), diesel::result::Error> {
+    use diesel::{Connection, ExecuteDsl, FilterDsl, ExpressionMethods};
+    use diesel::result::TransactionError;
+    use domain::schema::{pictures, picture_sizes, picture_derivatives};
+
+    let pid = &*p.id;
+    let res = conn.transaction(|| {
+        diesel::delete(picture_sizes::table.filter(picture_sizes::picture_id.eq(pid)))
+            .execute(conn)?;
+        diesel::delete(picture_derivatives::table.filter(picture_derivatives::picture_id.eq(pid)))
+            .execute(conn)?;
+        diesel::update(pictures::table.filter(pictures::duplicate_of.eq(pid)))
+            .set(pictures::duplicate_of.eq(None::<String>))
+            .execute(conn)?;
+        diesel::update(pictures::table.filter(pictures::id.eq(pid)))
+            .set((
+                pictures::status.eq(&p.status),
+                pictures::status_reason.eq(&p.status_reason),
+                pictures::status_changed_at.eq(&p.status_changed_at),
+                pictures::verified_at.eq(&p.verified_at)
+            )).execute(conn)?;
+        Ok(())
+    });
+    res.map_err(|e| match e {
+        TransactionError::CouldntCreateTransaction(e) => e,
+        TransactionError::UserReturnedError(e) => e,
+    })
+}
+
+pub fn list_pictures_by_status(conn: &PgConnection, pname: &str, s: &str) -> Vec<Picture> {
+    use diesel::{LoadDsl, FilterDsl, ExpressionMethods};
+    use domain::schema::pictures::dsl::{pictures, provider, status};
+    pictures.filter(provider.eq(pname))
+        .filter(status.eq(s))
+        .load::<Picture>(conn).unwrap()
+}
+
+pub fn list_pictures_by_monument_id(conn: &PgConnection, mid: &str) -> Vec<Picture> {
+    use diesel::{LoadDsl, FilterDsl, ExpressionMethods};
+    use domain::schema::pictures::dsl::{pictures, monument_id};
+    pictures.filter(monument_id.eq(mid)).load::<Picture>(conn).unwrap()
+}
+
+pub fn list_pictures(conn: &PgConnection) -> Vec<Picture> {
+    use diesel::{LoadDsl};
+    use domain::schema::pictures::dsl::{pictures};
+    pictures.load::<Picture>(conn).unwrap()
+}
+
+pub fn find_curation(conn: &PgConnection, pname: &str, eid: &str) -> Option<PictureCuration> {
+    use diesel::{LoadDsl, FilterDsl, ExpressionMethods};
+    use domain::schema::picture_curation::dsl::{picture_curation, provider, external_id};
+    picture_curation.filter(provider.eq(pname))
+        .filter(external_id.eq(eid))
+        .first::<PictureCuration>(conn).ok()
+}
+
+pub fn picture_rejected(conn: &PgConnection, pname: &str, eid: &str) -> bool {
+    find_curation(conn, pname, eid).map_or(false, |c| c.rejected)
+}
+
+pub fn save_curation(conn: &PgConnection, c: &PictureCuration) -> Result<(), diesel::result::Error> {
+    use diesel::{ExecuteDsl, FilterDsl, ExpressionMethods};
+    use domain::schema::picture_curation::dsl::*;
+
+    let updated = diesel::update(picture_curation.filter(id.eq(&c.id)))
+        .set((
+            pinned.eq(c.pinned),
+            rejected.eq(c.rejected),
+            sort_order.eq(&c.sort_order),
+            note.eq(&c.note),
+            updated_at.eq(c.updated_at)
+        )).execute(conn)?;
+    if updated == 0 {
+        diesel::insert(c).into(picture_curation).execute(conn)?;
+    }
+    Ok(())
+}
+
+pub fn list_curation_by_monument_id(conn: &PgConnection, mid: &str) -> Vec<PictureCuration> {
+    use diesel::{LoadDsl, FilterDsl, ExpressionMethods};
+    use domain::schema::picture_curation::dsl::{picture_curation, monument_id};
+    picture_curation.filter(monument_id.eq(mid)).load::<PictureCuration>(conn).unwrap()
+}
+
+pub fn find_picture(conn: &PgConnection, pid: &str) -> Option<Picture> {
+    use diesel::{LoadDsl, FilterDsl, ExpressionMethods};
+    use domain::schema::pictures::dsl::{pictures, id};
+    pictures.filter(id.eq(pid)).first::<Picture>(conn).ok()
+}
+
+pub fn find_monument_by_id_number(conn: &PgConnection, n: i32) -> Option<Monument> {
+    use diesel::{LoadDsl, FilterDsl, ExpressionMethods};
+    use domain::schema::monuments::dsl::{monuments, id_number};
+    monuments.filter(id_number.eq(n)).first::<Monument>(conn).ok()
+}
+
+pub fn list_search_overrides(conn: &PgConnection) -> Vec<SearchOverride> {
+    use diesel::{LoadDsl};
+    use domain::schema::search_overrides::dsl::{search_overrides};
+    search_overrides.load::<SearchOverride>(conn).unwrap()
+}
+
+pub fn save_search_override(conn: &PgConnection, o: &SearchOverride) -> Result<(), diesel::result::Error> {
+    use diesel::{ExecuteDsl, FilterDsl, ExpressionMethods};
+    use domain::schema::search_overrides::dsl::*;
+
+    let updated = diesel::update(search_overrides.filter(id_number.eq(o.id_number)))
+        .set((
+            query.eq(&o.query),
+            tags.eq(&o.tags),
+            bbox.eq(&o.bbox),
+            group_id.eq(&o.group_id),
+            updated_at.eq(o.updated_at)
+        )).execute(conn)?;
+    if updated == 0 {
+        diesel::insert(o).into(search_overrides).execute(conn)?;
+    }
+    Ok(())
+}
+
+pub fn list_monument_components(conn: &PgConnection) -> Vec<MonumentComponent> {
+    use diesel::{LoadDsl};
+    use domain::schema::monument_components::dsl::{monument_components};
+    monument_components.load::<MonumentComponent>(conn).unwrap()
+}
+
+pub fn list_monument_components_by_monument_id(conn: &PgConnection, mid: &str) -> Vec<MonumentComponent> {
+    use diesel::{LoadDsl, FilterDsl, ExpressionMethods};
+    use domain::schema::monument_components::dsl::{monument_components, monument_id};
+    monument_components.filter(monument_id.eq(mid)).load::<MonumentComponent>(conn).unwrap()
+}
+
+/// replace the components of a monument by `cs`
+pub fn replace_monument_components(conn: &PgConnection, mid: &str, cs: &[MonumentComponent])
+                                   -> Result<(), diesel::result::Error> {
+    use diesel::{Connection, ExecuteDsl, FilterDsl, ExpressionMethods};
+    use diesel::result::TransactionError;
+    use domain::schema::monument_components::dsl::*;
+
+    let res = conn.transaction(|| {
+        diesel::delete(monument_components.filter(monument_id.eq(mid)))
+            .execute(conn)?;
+        diesel::insert(cs).into(monument_components).execute(conn)?;
+        Ok(())
+    });
+    res.map_err(|e| match e {
+        TransactionError::CouldntCreateTransaction(e) => e,
+        TransactionError::UserReturnedError(e) => e,
+    })
+}
+
 pub fn list_monuments(conn: &PgConnection) -> Vec<Monument> {
     use diesel::{LoadDsl};
     use domain::schema::monuments::dsl::{monuments};
     monuments.load::<Monument>(conn).unwrap()
 }
 
+/// licenses of the flickr pictures are found by id, the others by name
+pub fn find_license(conn: &PgConnection, pname: &str, pl: &ProviderLicense) -> Option<License> {
+    use diesel::{LoadDsl, FilterDsl, ExpressionMethods};
+    use domain::schema::licenses::dsl::{licenses, provider, flickr_id, name};
+
+    match pl.flickr_id {
+        Some(fid) => licenses.filter(flickr_id.eq(fid)).first::<License>(conn).ok(),
+        None => licenses.filter(provider.eq(pname))
+            .filter(name.eq(&pl.name))
+            .first::<License>(conn).ok(),
+    }
+}
+
 pub fn list_licenses(conn: &PgConnection) -> Vec<License> {
     use diesel::{LoadDsl};
     use domain::schema::licenses::dsl::{licenses};
     licenses.load::<License>(conn).unwrap()
 }
+
+#[cfg(test)]
+mod tests {
+    use domain::{self, Monument};
+    use report::Report;
+    use super::*;
+    use test_db;
+
+    /// a distinct text for each field of each monument
+    fn set_texts(m: &mut Monument, n: i32) {
+        let text = |field: &str| Some(format!("{} {}", field, n));
+        m.criteria_txt = text("criteria_txt");
+        m.danger = text("danger");
+        m.historical_description = text("historical_description");
+        m.justification = text("justification");
+        m.location = text("location");
+        m.long_description = text("long_description");
+        m.secondary_dates = text("secondary_dates");
+        m.short_description = text("short_description");
+        m.site = text("site");
+        m.states = text("states");
+        m.short_description_html = text("short_description_html");
+        m.long_description_html = text("long_description_html");
+        m.justification_html = text("justification_html");
+        m.historical_description_html = text("historical_description_html");
+        m.short_description_markdown = text("short_description_markdown");
+        m.long_description_markdown = text("long_description_markdown");
+        m.justification_markdown = text("justification_markdown");
+        m.historical_description_markdown = text("historical_description_markdown");
+    }
+
+    #[test]
+    fn update_monuments_writes_every_text() {
+        test_db::with_connection(|conn| {
+            let numbers = [990101, 990102, 990103];
+            let new = numbers.iter()
+                .map(|&n| {
+                    let mut m = Monument::new();
+                    m.id_number = Some(n);
+                    m.category = Some("Cultural".into());
+                    m
+                })
+                .collect();
+            ::insert_monuments(conn, new, false, &mut Report::new(false)).unwrap();
+
+            let mut ms = numbers.iter()
+                .map(|&n| domain::dao::find_monument_by_id_number(conn, n).unwrap())
+                .collect::<Vec<_>>();
+            for m in ms.iter_mut() {
+                let n = m.id_number.unwrap();
+                set_texts(m, n);
+            }
+            assert_eq!(update_monuments(conn, &ms).unwrap(), ms.len());
+
+            for m in &ms {
+                let saved = domain::dao::find_monument_by_id_number(conn, m.id_number.unwrap()).unwrap();
+                // the other columns are left as they were
+                assert_eq!(&saved, m);
+            }
+            // and again in the same transaction
+            assert_eq!(update_monuments(conn, &ms[..1]).unwrap(), 1);
+            assert_eq!(update_monuments(conn, &[]).unwrap(), 0);
+        });
+    }
+}
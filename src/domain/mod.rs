@@ -8,16 +8,29 @@
 use chrono;
 use chrono::offset::utc::UTC;
 use flickr_api::License as RawLicense;
+use flickr_api::{PhotoInfo, Size};
+use html;
+use mirror::MirroredFile;
+use providers::{ProviderLicense, Size as ProviderSize};
+use providers::flickr::FLICKR;
+use self::attribution::Attribution;
 use self::schema::{
     licenses,
     monuments,
+    monument_components,
     pictures,
+    picture_sizes,
+    picture_derivatives,
+    picture_curation,
+    search_overrides,
+    task_runs,
     last_updates
 };
 use std::str::FromStr;
 use time::Duration;
 use uuid::Uuid;
 
+pub mod attribution;
 pub mod schema;
 pub mod dao;
 
@@ -55,6 +68,18 @@ pub struct Monument {
 
     pub created_at: chrono::NaiveDateTime,
     pub updated_at: chrono::NaiveDateTime,
+
+    /// sanitized html copies of the descriptions, only kept on demand
+    pub short_description_html: Option<String>,
+    pub long_description_html: Option<String>,
+    pub justification_html: Option<String>,
+    pub historical_description_html: Option<String>,
+
+    /// commonmark of the descriptions, for the clients showing formatted text
+    pub short_description_markdown: Option<String>,
+    pub long_description_markdown: Option<String>,
+    pub justification_markdown: Option<String>,
+    pub historical_description_markdown: Option<String>,
 }
 
 impl Monument {
@@ -86,7 +111,17 @@ impl Monument {
             unique_number: None,
 
             created_at: now(),
-            updated_at: now()
+            updated_at: now(),
+
+            short_description_html: None,
+            long_description_html: None,
+            justification_html: None,
+            historical_description_html: None,
+
+            short_description_markdown: None,
+            long_description_markdown: None,
+            justification_markdown: None,
+            historical_description_markdown: None,
         }
     }
 
@@ -120,27 +155,174 @@ impl Monument {
     }
 }
 
+impl Monument {
+    /// the monument located at one of its components, to search the
+    /// pictures of this component
+    pub fn at_component(&self, c: &MonumentComponent) -> Monument {
+        let mut m = self.clone();
+        m.site = Some(c.name.clone());
+        m.latitude = c.latitude;
+        m.longitude = c.longitude;
+        m
+    }
+
+    /// convert the html of the texts to plain text, after keeping a
+    /// sanitized copy of the descriptions if `keep_html` is set and
+    /// rendering them as markdown. returns the fields changed
+    pub fn clean_html(&mut self, keep_html: bool) -> Vec<&'static str> {
+        let mut changed = vec![];
+        if keep_html {
+            keep_copy(&mut changed, "short_description_html", html::sanitize,
+                      &self.short_description, &mut self.short_description_html);
+            keep_copy(&mut changed, "long_description_html", html::sanitize,
+                      &self.long_description, &mut self.long_description_html);
+            keep_copy(&mut changed, "justification_html", html::sanitize,
+                      &self.justification, &mut self.justification_html);
+            keep_copy(&mut changed, "historical_description_html", html::sanitize,
+                      &self.historical_description, &mut self.historical_description_html);
+        }
+        keep_markdown(&mut changed, "short_description_markdown", &self.short_description,
+                      &self.short_description_html, &mut self.short_description_markdown);
+        keep_markdown(&mut changed, "long_description_markdown", &self.long_description,
+                      &self.long_description_html, &mut self.long_description_markdown);
+        keep_markdown(&mut changed, "justification_markdown", &self.justification,
+                      &self.justification_html, &mut self.justification_markdown);
+        keep_markdown(&mut changed, "historical_description_markdown", &self.historical_description,
+                      &self.historical_description_html, &mut self.historical_description_markdown);
+        html_to_text(&mut changed, "criteria_txt", &mut self.criteria_txt);
+        html_to_text(&mut changed, "danger", &mut self.danger);
+        html_to_text(&mut changed, "historical_description", &mut self.historical_description);
+        html_to_text(&mut changed, "justification", &mut self.justification);
+        html_to_text(&mut changed, "location", &mut self.location);
+        html_to_text(&mut changed, "long_description", &mut self.long_description);
+        html_to_text(&mut changed, "secondary_dates", &mut self.secondary_dates);
+        html_to_text(&mut changed, "short_description", &mut self.short_description);
+        html_to_text(&mut changed, "site", &mut self.site);
+        html_to_text(&mut changed, "states", &mut self.states);
+        changed
+    }
+}
+
+fn non_empty(s: String) -> Option<String> {
+    if s.is_empty() { None } else { Some(s) }
+}
+
+/// the texts already converted are only trimmed, to keep their paragraphs
+fn html_to_text(changed: &mut Vec<&'static str>, name: &'static str, field: &mut Option<String>) {
+    let text = match *field {
+        Some(ref s) if html::is_html(s) => non_empty(html::to_text(s)),
+        Some(ref s) => non_empty(s.trim().to_string()),
+        None => None,
+    };
+    if text != *field {
+        *field = text;
+        changed.push(name);
+    }
+}
+
+/// only from an html text, a text already converted would replace the
+/// copy made by a previous import with a poorer one
+fn keep_copy(changed: &mut Vec<&'static str>, name: &'static str, convert: fn(&str) -> String,
+             from: &Option<String>, to: &mut Option<String>) {
+    let copy = match *from {
+        Some(ref s) if html::is_html(s) => non_empty(convert(s)),
+        _ => return,
+    };
+    if copy != *to {
+        *to = copy;
+        changed.push(name);
+    }
+}
+
+/// the markdown of the html text, else of the html copy kept by a previous
+/// import, else of the plain text
+fn keep_markdown(changed: &mut Vec<&'static str>, name: &'static str, from: &Option<String>,
+                 html_copy: &Option<String>, to: &mut Option<String>) {
+    let markdown = match (from, html_copy) {
+        (&Some(ref s), _) if html::is_html(s) => non_empty(html::to_markdown(s)),
+        (_, &Some(ref s)) => non_empty(html::to_markdown(s)),
+        (&Some(ref s), &None) => non_empty(html::text_to_markdown(s)),
+        (&None, &None) => return,
+    };
+    if markdown != *to {
+        *to = markdown;
+        changed.push(name);
+    }
+}
+
+/// a component site of a serial nomination
+#[derive(Clone, PartialEq, Debug, Queryable, Insertable)]
+#[table_name="monument_components"]
+pub struct MonumentComponent {
+    pub id: String,
+    pub monument_id: String,
+    /// the unesco reference of the component, like 1234-001
+    pub component_ref: Option<String>,
+    pub name: String,
+    pub latitude: Option<f32>,
+    pub longitude: Option<f32>,
+    /// in hectares
+    pub area: Option<f32>,
+
+    pub created_at: chrono::NaiveDateTime,
+    pub updated_at: chrono::NaiveDateTime,
+}
+
+impl MonumentComponent {
+    pub fn new<S: Into<String>>(monument_id: S, name: S) -> MonumentComponent {
+        MonumentComponent {
+            id: Uuid::new_v4().to_string(),
+            monument_id: monument_id.into(),
+            component_ref: None,
+            name: name.into(),
+            latitude: None,
+            longitude: None,
+            area: None,
+
+            created_at: now(),
+            updated_at: now()
+        }
+    }
+}
+
 #[derive(Clone, PartialEq, Debug, Queryable, Insertable)]
 #[table_name="licenses"]
 pub struct License {
     pub id: String,
-    pub flickr_id: i32,
+    pub flickr_id: Option<i32>,
     pub name: String,
     pub url: Option<String>,
 
     pub created_at: chrono::NaiveDateTime,
     pub updated_at: chrono::NaiveDateTime,
+
+    pub provider: String,
 }
 
 impl From<RawLicense> for License {
     fn from(rl: RawLicense) -> Self {
         License {
             id: String::new(),
-            flickr_id: rl.id,
+            flickr_id: Some(rl.id),
             name: rl.name,
             url: rl.url,
             created_at: now(),
-            updated_at: now()
+            updated_at: now(),
+            provider: FLICKR.into(),
+        }
+    }
+}
+
+impl License {
+    pub fn new<S: Into<String>>(provider: S, pl: &ProviderLicense) -> License {
+        License {
+            id: Uuid::new_v4().to_string(),
+            flickr_id: pl.flickr_id,
+            name: pl.name.clone(),
+            url: pl.url.clone(),
+            created_at: now(),
+            updated_at: now(),
+            provider: provider.into(),
         }
     }
 }
@@ -149,29 +331,227 @@ impl From<RawLicense> for License {
 #[table_name="pictures"]
 pub struct Picture {
     pub id: String,
-    pub flickr_id: String,
+    pub external_id: String,
     pub monument_id: String,
     pub license_id: String,
-    pub author: String,
     pub url: String,
+    pub author: String,
 
     pub created_at: chrono::NaiveDateTime,
     pub updated_at: chrono::NaiveDateTime,
+
+    pub title: Option<String>,
+    pub description: Option<String>,
+    pub date_taken: Option<chrono::NaiveDateTime>,
+    pub owner_nsid: Option<String>,
+    pub owner_realname: Option<String>,
+    pub page_url: Option<String>,
+    pub latitude: Option<f32>,
+    pub longitude: Option<f32>,
+    pub tags: Vec<String>,
+    pub original_width: Option<i32>,
+    pub original_height: Option<i32>,
+    pub blurhash: Option<String>,
+    pub dominant_color: Option<String>,
+    pub phash: Option<i64>,
+    pub duplicate_of: Option<String>,
+    pub status: String,
+    pub status_reason: Option<String>,
+    pub status_changed_at: Option<chrono::NaiveDateTime>,
+    pub verified_at: Option<chrono::NaiveDateTime>,
+    pub attribution_text: Option<String>,
+    pub attribution_html: Option<String>,
+    pub provider: String,
 }
 
+/// values of the `status` column of the pictures
+pub const PICTURE_ACTIVE: &'static str = "active";
+pub const PICTURE_DELETED: &'static str = "deleted";
+pub const PICTURE_PRIVATE: &'static str = "private";
+pub const PICTURE_RELICENSED: &'static str = "relicensed";
+pub const PICTURE_REJECTED: &'static str = "rejected";
+
 impl Picture {
-    pub fn new(flickr_id: String,
-               monument_id: String,
-               license_id: String,
-               author: String,
-               url: String) -> Picture {
+    pub fn new<P, E>(provider: P,
+                     external_id: E,
+                     monument_id: String,
+                     license_id: String,
+                     author: String,
+                     url: String) -> Picture
+        where P: Into<String>, E: Into<String> {
         Picture {
             id: Uuid::new_v4().to_string(),
-            flickr_id: flickr_id,
+            external_id: external_id.into(),
             monument_id: monument_id,
             license_id: license_id,
-            author: author,
             url: url,
+            author: author,
+
+            created_at: now(),
+            updated_at: now(),
+
+            title: None,
+            description: None,
+            date_taken: None,
+            owner_nsid: None,
+            owner_realname: None,
+            page_url: None,
+            latitude: None,
+            longitude: None,
+            tags: vec![],
+            original_width: None,
+            original_height: None,
+            blurhash: None,
+            dominant_color: None,
+            phash: None,
+            duplicate_of: None,
+            status: PICTURE_ACTIVE.into(),
+            status_reason: None,
+            status_changed_at: None,
+            verified_at: None,
+            attribution_text: None,
+            attribution_html: None,
+            provider: provider.into(),
+        }
+    }
+
+    pub fn set_status<S: Into<String>>(&mut self, status: &str, reason: S) {
+        self.status = status.into();
+        self.status_reason = Some(reason.into());
+        self.status_changed_at = Some(now());
+    }
+
+    pub fn set_verified(&mut self) {
+        self.verified_at = Some(now());
+    }
+
+    /// update the credit line, return true if it changed
+    pub fn set_attribution(&mut self, a: Attribution) -> bool {
+        let changed = self.attribution_text.as_ref() != Some(&a.text) ||
+            self.attribution_html.as_ref() != Some(&a.html);
+        self.attribution_text = Some(a.text);
+        self.attribution_html = Some(a.html);
+        changed
+    }
+
+    /// copy the metadata returned by flickr.photos.getInfo
+    pub fn set_info(&mut self, pi: &PhotoInfo) {
+        fn non_empty(s: &str) -> Option<String> {
+            if s.trim().is_empty() { None } else { Some(s.to_string()) }
+        }
+
+        self.author = pi.owner.username.clone();
+        self.title = non_empty(&*pi.title.content);
+        self.description = non_empty(&*pi.description.content);
+        self.date_taken = pi.dates.taken.as_ref().and_then(|d| {
+            chrono::NaiveDateTime::parse_from_str(&*d, "%Y-%m-%d %H:%M:%S").ok()
+        });
+        self.owner_nsid = Some(pi.owner.nsid.clone());
+        self.owner_realname = non_empty(&*pi.owner.realname);
+        self.page_url = pi.page_url();
+        match pi.location {
+            Some(ref l) => {
+                self.latitude = FromStr::from_str(&*l.latitude).ok();
+                self.longitude = FromStr::from_str(&*l.longitude).ok();
+            },
+            None => {
+                self.latitude = None;
+                self.longitude = None;
+            }
+        }
+        self.tags = pi.tags.tag.iter().map(|t| t.content.clone()).collect();
+    }
+
+    /// keep the dimensions of the original rendition from flickr.photos.getSizes
+    pub fn set_original_size(&mut self, sizes: &[Size]) {
+        match sizes.iter().find(|s| &*s.label == "Original") {
+            Some(s) => {
+                self.original_width = Some(s.width);
+                self.original_height = Some(s.height);
+            },
+            None => {}
+        }
+    }
+}
+
+/// one of the renditions flickr provides for a picture
+#[derive(Clone, PartialEq, Debug, Queryable, Insertable)]
+#[table_name="picture_sizes"]
+pub struct PictureSize {
+    pub id: String,
+    pub picture_id: String,
+    pub label: String,
+    pub width: i32,
+    pub height: i32,
+    pub url: String,
+
+    pub created_at: chrono::NaiveDateTime,
+    pub updated_at: chrono::NaiveDateTime,
+
+    pub sha256: Option<String>,
+    pub file_size: Option<i64>,
+    pub mime_type: Option<String>,
+    pub mirrored_at: Option<chrono::NaiveDateTime>,
+}
+
+impl PictureSize {
+    pub fn new<S: Into<String>>(picture_id: S, s: &ProviderSize) -> PictureSize {
+        PictureSize {
+            id: Uuid::new_v4().to_string(),
+            picture_id: picture_id.into(),
+            label: s.label.clone(),
+            width: s.width,
+            height: s.height,
+            url: s.url.clone(),
+
+            created_at: now(),
+            updated_at: now(),
+
+            sha256: None,
+            file_size: None,
+            mime_type: None,
+            mirrored_at: None,
+        }
+    }
+
+    pub fn set_mirrored(&mut self, f: &MirroredFile) {
+        self.sha256 = Some(f.sha256.clone());
+        self.file_size = Some(f.size);
+        self.mime_type = Some(f.mime_type.clone());
+        self.mirrored_at = Some(now());
+    }
+}
+
+/// a resized copy of a picture generated from its mirrored file
+#[derive(Clone, PartialEq, Debug, Queryable, Insertable)]
+#[table_name="picture_derivatives"]
+pub struct PictureDerivative {
+    pub id: String,
+    pub picture_id: String,
+    pub name: String,
+    pub width: i32,
+    pub height: i32,
+    pub sha256: String,
+    pub file_size: i64,
+    pub mime_type: String,
+
+    pub created_at: chrono::NaiveDateTime,
+    pub updated_at: chrono::NaiveDateTime,
+}
+
+impl PictureDerivative {
+    pub fn new<S, N>(picture_id: S, name: N, width: u32, height: u32, f: &MirroredFile)
+                     -> PictureDerivative
+        where S: Into<String>, N: Into<String> {
+        PictureDerivative {
+            id: Uuid::new_v4().to_string(),
+            picture_id: picture_id.into(),
+            name: name.into(),
+            width: width as i32,
+            height: height as i32,
+            sha256: f.sha256.clone(),
+            file_size: f.size,
+            mime_type: f.mime_type.clone(),
 
             created_at: now(),
             updated_at: now()
@@ -179,6 +559,163 @@ impl Picture {
     }
 }
 
+/// the choices of the editors for a picture
+#[derive(Clone, PartialEq, Debug, Queryable, Insertable)]
+#[table_name="picture_curation"]
+pub struct PictureCuration {
+    pub id: String,
+    pub provider: String,
+    pub external_id: String,
+    pub monument_id: String,
+    pub pinned: bool,
+    pub rejected: bool,
+    pub sort_order: Option<i32>,
+    pub note: Option<String>,
+
+    pub created_at: chrono::NaiveDateTime,
+    pub updated_at: chrono::NaiveDateTime,
+}
+
+impl PictureCuration {
+    pub fn new(p: &Picture) -> PictureCuration {
+        PictureCuration {
+            id: Uuid::new_v4().to_string(),
+            provider: p.provider.clone(),
+            external_id: p.external_id.clone(),
+            monument_id: p.monument_id.clone(),
+            pinned: false,
+            rejected: false,
+            sort_order: None,
+            note: None,
+
+            created_at: now(),
+            updated_at: now()
+        }
+    }
+
+    pub fn touch(&mut self) {
+        self.updated_at = now();
+    }
+}
+
+/// custom search of the pictures of a monument, used instead of its name
+/// and coordinates
+#[derive(Clone, PartialEq, Debug, Queryable, Insertable)]
+#[table_name="search_overrides"]
+pub struct SearchOverride {
+    pub id: String,
+    pub id_number: i32,
+    pub query: Option<String>,
+    pub tags: Vec<String>,
+    /// min_lon,min_lat,max_lon,max_lat
+    pub bbox: Option<String>,
+    pub group_id: Option<String>,
+
+    pub created_at: chrono::NaiveDateTime,
+    pub updated_at: chrono::NaiveDateTime,
+}
+
+impl SearchOverride {
+    pub fn new(id_number: i32) -> SearchOverride {
+        SearchOverride {
+            id: Uuid::new_v4().to_string(),
+            id_number: id_number,
+            query: None,
+            tags: vec![],
+            bbox: None,
+            group_id: None,
+
+            created_at: now(),
+            updated_at: now()
+        }
+    }
+
+    pub fn touch(&mut self) {
+        self.updated_at = now();
+    }
+
+    /// the corners of the bounding box: min_lon, min_lat, max_lon, max_lat
+    pub fn bbox(&self) -> Option<(f32, f32, f32, f32)> {
+        let v = match self.bbox {
+            Some(ref b) => b.split(',')
+                .map(|c| c.trim().parse::<f32>())
+                .collect::<Result<Vec<_>, _>>()
+                .ok(),
+            None => None,
+        };
+        match v {
+            Some(ref v) if v.len() == 4 => Some((v[0], v[1], v[2], v[3])),
+            _ => None,
+        }
+    }
+}
+
+/// the enrichment tasks run for each monument
+pub const TASK_PICTURES: &'static str = "pictures";
+
+/// freshness of an enrichment task of a monument
+#[derive(Clone, PartialEq, Debug, Queryable, Insertable)]
+#[table_name="task_runs"]
+pub struct TaskRun {
+    pub id: String,
+    pub monument_id: String,
+    pub task: String,
+    pub last_success_at: Option<chrono::NaiveDateTime>,
+    pub last_attempt_at: Option<chrono::NaiveDateTime>,
+    pub last_error: Option<String>,
+    pub empty_runs: i32,
+
+    pub created_at: chrono::NaiveDateTime,
+    pub updated_at: chrono::NaiveDateTime,
+}
+
+impl TaskRun {
+    pub fn new<S: Into<String>>(monument_id: S, task: S) -> TaskRun {
+        TaskRun {
+            id: Uuid::new_v4().to_string(),
+            monument_id: monument_id.into(),
+            task: task.into(),
+            last_success_at: None,
+            last_attempt_at: None,
+            last_error: None,
+            empty_runs: 0,
+
+            created_at: now(),
+            updated_at: now()
+        }
+    }
+
+    /// true if the task never succeeded or succeeded more than `window` ago
+    pub fn need_run(&self, window: Duration) -> bool {
+        match self.last_success_at {
+            Some(t) => t < now() - window,
+            None => true,
+        }
+    }
+
+    pub fn attempt(&mut self) {
+        self.last_attempt_at = Some(now());
+        self.updated_at = now();
+    }
+
+    /// `found` is the number of new things saved by the run
+    pub fn succeed(&mut self, found: usize) {
+        self.last_success_at = Some(now());
+        self.last_error = None;
+        self.updated_at = now();
+        if found > 0 {
+            self.empty_runs = 0;
+        } else {
+            self.empty_runs += 1;
+        }
+    }
+
+    pub fn fail<S: Into<String>>(&mut self, error: S) {
+        self.last_error = Some(error.into());
+        self.updated_at = now();
+    }
+}
+
 #[derive(Clone, PartialEq, Debug, Queryable, Insertable, Identifiable)]
 #[table_name="last_updates"]
 pub struct LastUpdate {
@@ -186,6 +723,7 @@ pub struct LastUpdate {
     pub monument_id: String,
     pub created_at: chrono::NaiveDateTime,
     pub updated_at: chrono::NaiveDateTime,
+    pub empty_runs: i32,
 }
 
 impl LastUpdate {
@@ -194,17 +732,19 @@ impl LastUpdate {
             id: Uuid::new_v4().to_string(),
             monument_id: monument_id.into(),
             created_at: now(),
-            updated_at: now() - Duration::days(365),
+            // never refreshed, whatever the freshness policy
+            updated_at: chrono::NaiveDateTime::from_timestamp(0, 0),
+            empty_runs: 0,
         }
     }
 
-    pub fn need_refresh(&self) -> bool {
-        // reupdate if last update is more than a week ago
-        let a_week_ago = now() - Duration::days(7);
-        self.updated_at < a_week_ago
-    }
-
-    pub fn set_fresh(&mut self) {
+    /// `found` is the number of new pictures saved by the refresh
+    pub fn set_fresh(&mut self, found: usize) {
         self.updated_at = now();
+        if found > 0 {
+            self.empty_runs = 0;
+        } else {
+            self.empty_runs += 1;
+        }
     }
 }
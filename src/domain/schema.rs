@@ -1 +1,198 @@
-infer_schema!("dotenv:DATABASE_URL");
+// Copyright 2017 Jeremy Letang.
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+
+//! The tables created by the migrations, written out so the crate builds
+//! without a database.
+//!
+//! After adding a migration, apply it then replace the tables below with
+//! the output of `whlp migrate print-schema`. `whlp migrate check-schema`
+//! fails when they differ from the database, run it on a freshly migrated
+//! database to check this file.
+
+table! {
+    last_updates (id) {
+        id -> VarChar,
+        monument_id -> VarChar,
+        created_at -> Timestamp,
+        updated_at -> Timestamp,
+        empty_runs -> Int4,
+    }
+}
+
+table! {
+    licenses (id) {
+        id -> VarChar,
+        flickr_id -> Nullable<Int4>,
+        name -> Text,
+        url -> Nullable<Text>,
+        created_at -> Timestamp,
+        updated_at -> Timestamp,
+        provider -> VarChar,
+    }
+}
+
+table! {
+    monument_components (id) {
+        id -> VarChar,
+        monument_id -> VarChar,
+        component_ref -> Nullable<Text>,
+        name -> Text,
+        latitude -> Nullable<Float4>,
+        longitude -> Nullable<Float4>,
+        area -> Nullable<Float4>,
+        created_at -> Timestamp,
+        updated_at -> Timestamp,
+    }
+}
+
+table! {
+    monuments (id) {
+        id -> VarChar,
+        category -> Nullable<Text>,
+        criteria_txt -> Nullable<Text>,
+        danger -> Nullable<Text>,
+        date_inscribed -> Nullable<Text>,
+        extension -> Nullable<Int4>,
+        historical_description -> Nullable<Text>,
+        http_url -> Nullable<Text>,
+        id_number -> Nullable<Int4>,
+        image_url -> Nullable<Text>,
+        iso_code -> Nullable<Text>,
+        justification -> Nullable<Text>,
+        latitude -> Nullable<Float4>,
+        longitude -> Nullable<Float4>,
+        location -> Nullable<Text>,
+        long_description -> Nullable<Text>,
+        region -> Nullable<Text>,
+        revision -> Nullable<Int4>,
+        secondary_dates -> Nullable<Text>,
+        short_description -> Nullable<Text>,
+        site -> Nullable<Text>,
+        states -> Nullable<Text>,
+        transboundary -> Nullable<Int4>,
+        unique_number -> Nullable<Int4>,
+        created_at -> Timestamp,
+        updated_at -> Timestamp,
+        short_description_html -> Nullable<Text>,
+        long_description_html -> Nullable<Text>,
+        justification_html -> Nullable<Text>,
+        historical_description_html -> Nullable<Text>,
+        short_description_markdown -> Nullable<Text>,
+        long_description_markdown -> Nullable<Text>,
+        justification_markdown -> Nullable<Text>,
+        historical_description_markdown -> Nullable<Text>,
+    }
+}
+
+table! {
+    picture_curation (id) {
+        id -> VarChar,
+        provider -> VarChar,
+        external_id -> VarChar,
+        monument_id -> VarChar,
+        pinned -> Bool,
+        rejected -> Bool,
+        sort_order -> Nullable<Int4>,
+        note -> Nullable<Text>,
+        created_at -> Timestamp,
+        updated_at -> Timestamp,
+    }
+}
+
+table! {
+    picture_derivatives (id) {
+        id -> VarChar,
+        picture_id -> VarChar,
+        name -> Text,
+        width -> Int4,
+        height -> Int4,
+        sha256 -> VarChar,
+        file_size -> Int8,
+        mime_type -> Text,
+        created_at -> Timestamp,
+        updated_at -> Timestamp,
+    }
+}
+
+table! {
+    picture_sizes (id) {
+        id -> VarChar,
+        picture_id -> VarChar,
+        label -> Text,
+        width -> Int4,
+        height -> Int4,
+        url -> Text,
+        created_at -> Timestamp,
+        updated_at -> Timestamp,
+        sha256 -> Nullable<VarChar>,
+        file_size -> Nullable<Int8>,
+        mime_type -> Nullable<Text>,
+        mirrored_at -> Nullable<Timestamp>,
+    }
+}
+
+table! {
+    pictures (id) {
+        id -> VarChar,
+        external_id -> VarChar,
+        monument_id -> VarChar,
+        license_id -> VarChar,
+        url -> Text,
+        author -> Text,
+        created_at -> Timestamp,
+        updated_at -> Timestamp,
+        title -> Nullable<Text>,
+        description -> Nullable<Text>,
+        date_taken -> Nullable<Timestamp>,
+        owner_nsid -> Nullable<Text>,
+        owner_realname -> Nullable<Text>,
+        page_url -> Nullable<Text>,
+        latitude -> Nullable<Float4>,
+        longitude -> Nullable<Float4>,
+        tags -> Array<Text>,
+        original_width -> Nullable<Int4>,
+        original_height -> Nullable<Int4>,
+        blurhash -> Nullable<Text>,
+        dominant_color -> Nullable<VarChar>,
+        phash -> Nullable<Int8>,
+        duplicate_of -> Nullable<VarChar>,
+        status -> VarChar,
+        status_reason -> Nullable<Text>,
+        status_changed_at -> Nullable<Timestamp>,
+        verified_at -> Nullable<Timestamp>,
+        attribution_text -> Nullable<Text>,
+        attribution_html -> Nullable<Text>,
+        provider -> VarChar,
+    }
+}
+
+table! {
+    search_overrides (id) {
+        id -> VarChar,
+        id_number -> Int4,
+        query -> Nullable<Text>,
+        tags -> Array<Text>,
+        bbox -> Nullable<Text>,
+        group_id -> Nullable<Text>,
+        created_at -> Timestamp,
+        updated_at -> Timestamp,
+    }
+}
+
+table! {
+    task_runs (id) {
+        id -> VarChar,
+        monument_id -> VarChar,
+        task -> Text,
+        last_success_at -> Nullable<Timestamp>,
+        last_attempt_at -> Nullable<Timestamp>,
+        last_error -> Nullable<Text>,
+        empty_runs -> Int4,
+        created_at -> Timestamp,
+        updated_at -> Timestamp,
+    }
+}
@@ -0,0 +1,135 @@
+// Copyright 2017 Jeremy Letang.
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+
+//! Write the monuments with their visible pictures as json, in the order
+//! shown by the clients.
+
+use curation;
+use diesel::pg::PgConnection;
+use domain::{self, License, Monument, Picture};
+use serde_json;
+use std::collections::HashMap;
+use std::fs::File;
+use std::io::{self, Write};
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct ExportedLicense {
+    pub name: String,
+    pub url: Option<String>,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct ExportedPicture {
+    pub id: String,
+    pub provider: String,
+    pub external_id: String,
+    pub url: String,
+    pub page_url: Option<String>,
+    pub title: Option<String>,
+    pub author: String,
+    pub width: Option<i32>,
+    pub height: Option<i32>,
+    pub blurhash: Option<String>,
+    pub dominant_color: Option<String>,
+    pub license: Option<ExportedLicense>,
+    pub attribution_text: Option<String>,
+    pub attribution_html: Option<String>,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct ExportedMonument {
+    pub id: String,
+    pub id_number: Option<i32>,
+    pub site: Option<String>,
+    pub category: Option<String>,
+    pub states: Option<String>,
+    pub region: Option<String>,
+    pub latitude: Option<f32>,
+    pub longitude: Option<f32>,
+    pub date_inscribed: Option<String>,
+    pub danger: Option<String>,
+    pub http_url: Option<String>,
+    pub short_description: Option<String>,
+    pub short_description_markdown: Option<String>,
+    pub long_description_markdown: Option<String>,
+    pub pictures: Vec<ExportedPicture>,
+}
+
+fn export_picture(p: Picture, licenses: &HashMap<String, License>) -> ExportedPicture {
+    let license = licenses.get(&p.license_id).map(|l| ExportedLicense {
+        name: l.name.clone(),
+        url: l.url.clone(),
+    });
+    ExportedPicture {
+        id: p.id,
+        provider: p.provider,
+        external_id: p.external_id,
+        url: p.url,
+        page_url: p.page_url,
+        title: p.title,
+        author: p.author,
+        width: p.original_width,
+        height: p.original_height,
+        blurhash: p.blurhash,
+        dominant_color: p.dominant_color,
+        license: license,
+        attribution_text: p.attribution_text,
+        attribution_html: p.attribution_html,
+    }
+}
+
+fn export_monument(conn: &PgConnection, m: Monument, licenses: &HashMap<String, License>)
+                   -> ExportedMonument {
+    // only the pictures the clients show
+    let pictures = curation::ordered(conn, &*m.id)
+        .into_iter()
+        .filter(|&(ref p, _)| &*p.status == domain::PICTURE_ACTIVE && p.duplicate_of.is_none())
+        .map(|(p, _)| export_picture(p, licenses))
+        .collect();
+    ExportedMonument {
+        id: m.id,
+        id_number: m.id_number,
+        site: m.site,
+        category: m.category,
+        states: m.states,
+        region: m.region,
+        latitude: m.latitude,
+        longitude: m.longitude,
+        date_inscribed: m.date_inscribed,
+        danger: m.danger,
+        http_url: m.http_url,
+        short_description: m.short_description,
+        short_description_markdown: m.short_description_markdown,
+        long_description_markdown: m.long_description_markdown,
+        pictures: pictures,
+    }
+}
+
+/// write the export in `output`, or on the standard output
+pub fn run(conn: &PgConnection, output: Option<String>) -> Result<(), String> {
+    let licenses = domain::dao::list_licenses(conn)
+        .into_iter()
+        .map(|l| (l.id.clone(), l))
+        .collect::<HashMap<_, _>>();
+    let mut monuments = domain::dao::list_monuments(conn);
+    monuments.sort_by_key(|m| m.id_number);
+    let exported = monuments.into_iter()
+        .map(|m| export_monument(conn, m, &licenses))
+        .collect::<Vec<_>>();
+
+    let json = serde_json::to_string_pretty(&exported)
+        .map_err(|e| format!("unable to serialize export, {}", e))?;
+    match output {
+        Some(f) => File::create(&f)
+            .and_then(|mut w| w.write_all(json.as_bytes()))
+            .map_err(|e| format!("unable to write export {}, {}", f, e))?,
+        None => io::stdout().write_all(json.as_bytes())
+            .map_err(|e| format!("unable to write export, {}", e))?,
+    }
+    info!("{} monuments exported", exported.len());
+    Ok(())
+}
@@ -5,10 +5,11 @@
 // option. This file may not be copied, modified, or distributed
 // except according to those terms.
 
-use reqwest::Client;
+use http;
+use reqwest::Url;
+use serde::{de, Deserializer};
 use serde_json;
-use std::error::Error;
-use std::io::Read;
+use std::fmt;
 
 #[derive(Clone, Debug, Serialize, Deserialize)]
 pub struct License {
@@ -30,28 +31,7 @@ pub struct LicensesPayload {
 pub fn get_licenses(key: &str) -> Result<Vec<License>, String> {
     info!("calling flickr.photos.licenses.getInfo api");
     let url = format!("https://api.flickr.com/services/rest/?method=flickr.photos.licenses.getInfo&api_key={}&format=json&nojsoncallback=1", key);
-    match Client::new().unwrap().get(&*url).send() {
-        Ok(mut r) => {
-            if r.status().is_success() {
-                let mut buf = String::new();
-                match r.read_to_string(&mut buf) {
-                    Ok(_) => {
-                        // unserialize
-                        match serde_json::from_str::<LicensesPayload>(&*buf) {
-                            Ok(v) => Ok(v.licenses.license),
-                            Err(e) => {
-                                Err(format!("unable to deserialize payload, try again, {}", e))
-                            }
-                        }
-                    },
-                    Err(e) => Err(format!("unable to read http request payload, try again, {}", e))
-                }
-            } else {
-                Err(format!("unexpected http status, try again"))
-            }
-        },
-        Err(e) => Err(format!("unable to get flickr licenses, {}, {:?}", e.description(), e.cause()))
-    }
+    http::get_json::<LicensesPayload>(&*url, "flickr licenses").map(|v| v.licenses.license)
 }
 
 #[derive(Clone, Debug, Serialize, Deserialize)]
@@ -77,47 +57,98 @@ pub enum FindByLatLonError {
 pub fn get_place(key: &str, lat: f32, lng: f32) -> Result<String, FindByLatLonError> {
     info!("calling flickr.places.findByLatLon api");
     let url = format!("https://api.flickr.com/services/rest/?method=flickr.places.findByLatLon&api_key={}&lat={}&lon={}&format=json&nojsoncallback=1", key, lat, lng);
-    match Client::new().unwrap().get(&*url).send() {
-        Ok(mut r) => {
-            if r.status().is_success() {
-                let mut buf = String::new();
-                match r.read_to_string(&mut buf) {
-                    Ok(_) => {
-                        match serde_json::from_str::<FindByLatLonPayload>(&*buf) {
-                            Ok(v) => {
-                                if v.places.place.len() > 0 {
-                                    Ok(v.places.place[0].place_id.clone())
-                                } else {
-                                    Err(FindByLatLonError::NoMatchingPlace)
-                                }
-                            },
-                            Err(e) => Err(FindByLatLonError::RequestError(
-                                format!("unable to deserialize payload, try again, {}", e)))
-                        }
-                    },
-                    Err(e) => Err(FindByLatLonError::RequestError(
-                        format!("unable to read http request payload, try again, {}", e)))
-                }
+    match http::get_json::<FindByLatLonPayload>(&*url, "flickr places") {
+        Ok(v) => {
+            if v.places.place.len() > 0 {
+                Ok(v.places.place[0].place_id.clone())
             } else {
-                Err(FindByLatLonError::RequestError(format!("unexpected http status, try again")))
+                Err(FindByLatLonError::NoMatchingPlace)
             }
         },
-        Err(e) => Err(FindByLatLonError::RequestError(
-            format!("unable to get flickr groupd, {}", e.description())))
+        Err(e) => Err(FindByLatLonError::RequestError(e)),
     }
 }
 
 #[derive(Clone, Debug, Serialize, Deserialize)]
 pub struct Owner {
+    pub nsid: String,
     pub username: String,
+    #[serde(default)]
+    pub realname: String,
+}
+
+/// flickr wrap most of its text values in an object with a `_content` field
+#[derive(Clone, Debug, Default, Serialize, Deserialize)]
+pub struct Content {
+    #[serde(rename = "_content")]
+    pub content: String,
+}
+
+#[derive(Clone, Debug, Default, Serialize, Deserialize)]
+pub struct Dates {
+    pub taken: Option<String>,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct Tag {
+    pub raw: String,
+    #[serde(rename = "_content")]
+    pub content: String,
+}
+
+#[derive(Clone, Debug, Default, Serialize, Deserialize)]
+pub struct Tags {
+    #[serde(default)]
+    pub tag: Vec<Tag>,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct Location {
+    pub latitude: String,
+    pub longitude: String,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct Url {
+    #[serde(rename = "type")]
+    pub kind: String,
+    #[serde(rename = "_content")]
+    pub content: String,
+}
+
+#[derive(Clone, Debug, Default, Serialize, Deserialize)]
+pub struct Urls {
+    #[serde(default)]
+    pub url: Vec<Url>,
 }
 
 #[derive(Clone, Debug, Serialize, Deserialize)]
 pub struct PhotoInfo {
     pub id: String,
+    pub farm: i32,
+    pub server: String,
     pub originalsecret: String,
     pub license: i32,
     pub owner: Owner,
+    #[serde(default)]
+    pub title: Content,
+    #[serde(default)]
+    pub description: Content,
+    #[serde(default)]
+    pub dates: Dates,
+    #[serde(default)]
+    pub tags: Tags,
+    pub location: Option<Location>,
+    #[serde(default)]
+    pub urls: Urls,
+}
+
+impl PhotoInfo {
+    pub fn page_url(&self) -> Option<String> {
+        self.urls.url.iter()
+            .find(|u| &*u.kind == "photopage")
+            .map(|u| u.content.clone())
+    }
 }
 
 #[derive(Clone, Debug, Serialize, Deserialize)]
@@ -125,33 +156,110 @@ pub struct GetInfoPhotoPayload {
     pub photo: PhotoInfo,
 }
 
+pub enum PhotoInfoError {
+    RequestError(String),
+    NotFound,
+    PermissionDenied,
+}
+
+/// body of the error payloads, flickr answer them with a 200 status
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct Stat {
+    pub stat: String,
+    pub code: Option<i32>,
+    pub message: Option<String>,
+}
+
 pub fn get_photo_info(key: &str, photo_id: &str) -> Result<PhotoInfo, String> {
+    match get_photo_status(key, photo_id) {
+        Ok(pi) => Ok(pi),
+        Err(PhotoInfoError::RequestError(e)) => Err(e),
+        Err(PhotoInfoError::NotFound) => Err(format!("photo {} not found", photo_id)),
+        Err(PhotoInfoError::PermissionDenied) => Err(format!("photo {} is not public", photo_id)),
+    }
+}
+
+/// like get_photo_info but tell apart the photos which are deleted or
+/// not visible anymore from the request errors
+pub fn get_photo_status(key: &str, photo_id: &str) -> Result<PhotoInfo, PhotoInfoError> {
     info!("calling flickr.photos.getInfo api");
     let url = format!("https://api.flickr.com/services/rest/?method=flickr.photos.getInfo&api_key={}&photo_id={}&format=json&nojsoncallback=1", key, photo_id);
 
-    match Client::new().unwrap().get(&*url).send() {
-        Ok(mut r) => {
-            if r.status().is_success() {
-                let mut buf = String::new();
-                match r.read_to_string(&mut buf) {
-                    Ok(_) => {
-                        info!("body: {}", buf);
-                        // unserialize
-                        match serde_json::from_str::<GetInfoPhotoPayload>(&*buf) {
-                            Ok(v) => Ok(v.photo),
-                            Err(e) => {
-                                Err(format!("unable to deserialize payload, try again, {}", e))
-                            }
-                        }
-                    },
-                    Err(e) => Err(format!("unable to read http request payload, try again, {}", e))
-                }
-            } else {
-                Err(format!("unexpected http status, try again"))
+    let buf = match http::get_body(&*url, "flickr photo info") {
+        Ok(buf) => buf,
+        Err(e) => return Err(PhotoInfoError::RequestError(e)),
+    };
+    debug!("body: {}", buf);
+    match serde_json::from_str::<Stat>(&*buf) {
+        Ok(ref s) if &*s.stat == "fail" => {
+            return match s.code {
+                Some(1) => Err(PhotoInfoError::NotFound),
+                Some(2) => Err(PhotoInfoError::PermissionDenied),
+                _ => Err(PhotoInfoError::RequestError(
+                    format!("flickr error, {}", s.message.clone().unwrap_or_default()))),
             }
         },
-        Err(e) => Err(format!("unable to get flickr photo info, {}", e.description()))
+        _ => {}
+    }
+    match serde_json::from_str::<GetInfoPhotoPayload>(&*buf) {
+        Ok(v) => Ok(v.photo),
+        Err(e) => Err(PhotoInfoError::RequestError(
+            format!("unable to deserialize payload, try again, {}", e)))
+    }
+}
+
+/// flickr returns the sizes either as json numbers or as strings depending
+/// on the rendition, accept both
+fn int_or_string<D: Deserializer>(d: D) -> Result<i32, D::Error> {
+    struct IntOrString;
+
+    impl de::Visitor for IntOrString {
+        type Value = i32;
+
+        fn expecting(&self, f: &mut fmt::Formatter) -> fmt::Result {
+            f.write_str("an integer or a string containing an integer")
+        }
+
+        fn visit_i64<E: de::Error>(self, v: i64) -> Result<i32, E> {
+            Ok(v as i32)
+        }
+
+        fn visit_u64<E: de::Error>(self, v: u64) -> Result<i32, E> {
+            Ok(v as i32)
+        }
+
+        fn visit_str<E: de::Error>(self, v: &str) -> Result<i32, E> {
+            v.parse::<i32>().map_err(|e| E::custom(format!("{}", e)))
+        }
     }
+
+    d.deserialize(IntOrString)
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct Size {
+    pub label: String,
+    #[serde(deserialize_with = "int_or_string")]
+    pub width: i32,
+    #[serde(deserialize_with = "int_or_string")]
+    pub height: i32,
+    pub source: String,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct Sizes {
+    pub size: Vec<Size>,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct GetSizesPayload {
+    pub sizes: Sizes,
+}
+
+pub fn get_sizes(key: &str, photo_id: &str) -> Result<Vec<Size>, String> {
+    info!("calling flickr.photos.getSizes api");
+    let url = format!("https://api.flickr.com/services/rest/?method=flickr.photos.getSizes&api_key={}&photo_id={}&format=json&nojsoncallback=1", key, photo_id);
+    http::get_json::<GetSizesPayload>(&*url, "flickr photo sizes").map(|v| v.sizes.size)
 }
 
 #[derive(Clone, Debug, Serialize, Deserialize)]
@@ -172,18 +280,64 @@ pub struct SearchPhotosPayload {
     pub photos: Photos,
 }
 
-pub fn search_photos(key: &str, search_str: String, place_id: Option<String>)
+/// licenses of the photos we harvest by default, the free ones
+pub const HARVESTED_LICENSES: [i32; 9] = [1, 2, 3, 4, 5, 6, 7, 9, 10];
+
+pub fn search_photos(key: &str, search_str: String, place_id: Option<String>, licenses: &[i32])
                      -> Result<Vec<Photo>, String> {
     info!("calling flickr.photos.search api");
     let search_str = search_str.replace(" ", "+");
     let with_place_id = match place_id {
-        Some(pid) => _search_photos(key, &*search_str, &*pid),
+        Some(pid) => _search_photos(key, &*search_str, &*pid, licenses),
         None => Ok(vec![]),
     };
-    let without_place_id = _search_photos(key, &*search_str, "");
+    let without_place_id = _search_photos(key, &*search_str, "", licenses);
     resolve_smallest_photos(with_place_id, without_place_id)
 }
 
+/// search parameters set by hand for the monuments the default search
+/// cannot find
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct SearchFilters {
+    pub text: Option<String>,
+    pub tags: Vec<String>,
+    /// min_lon,min_lat,max_lon,max_lat
+    pub bbox: Option<String>,
+    pub group_id: Option<String>,
+}
+
+pub fn search_photos_with(key: &str, f: &SearchFilters, licenses: &[i32]) -> Result<Vec<Photo>, String> {
+    info!("calling flickr.photos.search api with filters");
+    let licenses = licenses.iter().map(|l| l.to_string()).collect::<Vec<_>>().join(",");
+    let mut url = Url::parse("https://api.flickr.com/services/rest/").unwrap();
+    url.query_pairs_mut()
+        .append_pair("method", "flickr.photos.search")
+        .append_pair("per_page", "10")
+        .append_pair("api_key", key)
+        .append_pair("license", &*licenses)
+        .append_pair("format", "json")
+        .append_pair("nojsoncallback", "1");
+    match f.text {
+        Some(ref t) => { url.query_pairs_mut().append_pair("text", t); },
+        None => {},
+    }
+    if !f.tags.is_empty() {
+        url.query_pairs_mut()
+            .append_pair("tags", &*f.tags.join(","))
+            .append_pair("tag_mode", "all");
+    }
+    match f.bbox {
+        Some(ref b) => { url.query_pairs_mut().append_pair("bbox", b); },
+        None => {},
+    }
+    match f.group_id {
+        Some(ref g) => { url.query_pairs_mut().append_pair("group_id", g); },
+        None => {},
+    }
+
+    http::get_json::<SearchPhotosPayload>(url.as_str(), "flickr photos").map(|v| v.photos.photo)
+}
+
 fn resolve_smallest_photos(p1: Result<Vec<Photo>, String>, p2: Result<Vec<Photo>, String>)
                            -> Result<Vec<Photo>, String> {
     if p1.is_err() {
@@ -203,30 +357,10 @@ fn resolve_smallest_photos(p1: Result<Vec<Photo>, String>, p2: Result<Vec<Photo>
     }
 }
 
-fn _search_photos(key: &str, search_str: &str, place_id: &str) -> Result<Vec<Photo>, String> {
-    let url = format!("https://api.flickr.com/services/rest/?method=flickr.photos.search&per_page=10&api_key={}&text={}&license=1%2C2%2C3%2C4%2C5%2C6%2C7%2C9%2C10&place_id={}&format=json&nojsoncallback=1", key, search_str, place_id);
-
-    match Client::new().unwrap().get(&*url).send() {
-        Ok(mut r) => {
-            if r.status().is_success() {
-                let mut buf = String::new();
-                match r.read_to_string(&mut buf) {
-                    Ok(_) => {
-                        // unserialize
-                        match serde_json::from_str::<SearchPhotosPayload>(&*buf) {
-                            Ok(v) => Ok(v.photos.photo),
-                            Err(e) => {
-                                Err(format!("unable to deserialize payload, try again, {}", e))
-                            }
-                        }
-                    },
-                    Err(e) => Err(format!("unable to read http request payload, try again, {}", e))
-                }
-            } else {
-                Err(format!("unexpected http status, try again"))
-            }
-        },
-        Err(e) => Err(format!("unable to get flickr photos, {}", e.description()))
-    }
+fn _search_photos(key: &str, search_str: &str, place_id: &str, licenses: &[i32])
+                  -> Result<Vec<Photo>, String> {
+    let licenses = licenses.iter().map(|l| l.to_string()).collect::<Vec<_>>().join("%2C");
+    let url = format!("https://api.flickr.com/services/rest/?method=flickr.photos.search&per_page=10&api_key={}&text={}&license={}&place_id={}&format=json&nojsoncallback=1", key, search_str, licenses, place_id);
 
+    http::get_json::<SearchPhotosPayload>(&*url, "flickr photos").map(|v| v.photos.photo)
 }
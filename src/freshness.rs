@@ -0,0 +1,91 @@
+// Copyright 2017 Jeremy Letang.
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+
+//! How often the pictures of a monument are searched again.
+//!
+//! The refresh window of a monument is, in order of preference, the one of
+//! the monument, the one of its category or the default one. It doubles for
+//! each refresh in a row which found no new picture, up to `max_days`.
+//!
+//! ```json
+//! {
+//!   "max_days": 90,
+//!   "categories": { "Natural": 30 },
+//!   "monuments": { "668": 1 }
+//! }
+//! ```
+
+use domain::{Monument, TaskRun};
+use serde_json;
+use std::collections::HashMap;
+use std::fs::File;
+use std::io::Read;
+use time::Duration;
+
+fn default_max_days() -> i64 { 90 }
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct Policy {
+    /// set from the command line
+    #[serde(skip_deserializing, default)]
+    pub default_days: i64,
+    /// the longest window reached by the backoff
+    #[serde(default = "default_max_days")]
+    pub max_days: i64,
+    /// windows by category of monument
+    #[serde(default)]
+    pub categories: HashMap<String, i64>,
+    /// windows by `id_number` of monument
+    #[serde(default)]
+    pub monuments: HashMap<String, i64>,
+    /// `id_number` of the monuments refreshed whatever their last refresh
+    #[serde(skip_deserializing, default)]
+    pub force: Vec<i32>,
+}
+
+impl Policy {
+    pub fn new(default_days: i64) -> Policy {
+        Policy {
+            default_days: default_days,
+            max_days: default_max_days(),
+            categories: HashMap::new(),
+            monuments: HashMap::new(),
+            force: vec![],
+        }
+    }
+
+    pub fn from_file(file: &str, default_days: i64) -> Result<Policy, String> {
+        info!("reading refresh policy: '{}'", file);
+        let mut buf = String::new();
+        match File::open(file).and_then(|mut f| f.read_to_string(&mut buf)) {
+            Ok(_) => serde_json::from_str::<Policy>(&*buf)
+                .map(|mut p| { p.default_days = default_days; p })
+                .map_err(|e| format!("invalid refresh policy, {}", e)),
+            Err(e) => Err(format!("unable to read refresh policy, {}", e)),
+        }
+    }
+
+    /// the refresh window of the monument, before backoff
+    pub fn days(&self, m: &Monument) -> i64 {
+        let by_monument = m.id_number.and_then(|n| self.monuments.get(&n.to_string()));
+        let by_category = m.category.as_ref().and_then(|c| self.categories.get(c));
+        *by_monument.or(by_category).unwrap_or(&self.default_days)
+    }
+
+    /// the refresh window of the monument, doubled for each empty refresh
+    pub fn window(&self, m: &Monument, r: &TaskRun) -> Duration {
+        let base = self.days(m);
+        // 2^6 is already far above any sensible max_days
+        let factor = 1i64 << r.empty_runs.max(0).min(6);
+        Duration::days((base * factor).min(self.max_days.max(base)))
+    }
+
+    pub fn need_refresh(&self, m: &Monument, r: &TaskRun) -> bool {
+        let forced = m.id_number.map_or(false, |n| self.force.contains(&n));
+        forced || r.need_run(self.window(m, r))
+    }
+}
@@ -0,0 +1,756 @@
+// Copyright 2017 Jeremy Letang.
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+
+//! Convert the html of the whc texts to plain text, or to a small subset
+//! of html safe to show as is.
+
+use std::char;
+use std::cmp;
+
+/// tags separating paragraphs
+const BLOCK_TAGS: [&'static str; 15] = [
+    "p", "div", "h1", "h2", "h3", "h4", "h5", "h6", "ul", "ol", "table",
+    "blockquote", "section", "article", "header",
+];
+
+/// tags starting a new line
+const LINE_TAGS: [&'static str; 4] = ["li", "tr", "dt", "dd"];
+
+/// tags whose content is never text
+const SKIPPED_TAGS: [&'static str; 3] = ["script", "style", "head"];
+
+/// tags kept by `sanitize`, without their attributes except the links
+const SAFE_TAGS: [&'static str; 11] = [
+    "p", "br", "em", "strong", "i", "b", "ul", "ol", "li", "a", "blockquote",
+];
+
+const ENTITIES: [(&'static str, &'static str); 52] = [
+    ("amp", "&"), ("lt", "<"), ("gt", ">"), ("quot", "\""), ("apos", "'"),
+    ("nbsp", " "), ("ndash", "\u{2013}"), ("mdash", "\u{2014}"), ("lsquo", "\u{2018}"),
+    ("rsquo", "\u{2019}"), ("ldquo", "\u{201c}"), ("rdquo", "\u{201d}"), ("hellip", "\u{2026}"),
+    ("laquo", "\u{ab}"), ("raquo", "\u{bb}"), ("copy", "\u{a9}"), ("reg", "\u{ae}"),
+    ("deg", "\u{b0}"), ("middot", "\u{b7}"), ("times", "\u{d7}"), ("szlig", "\u{df}"),
+    ("aacute", "\u{e1}"), ("agrave", "\u{e0}"), ("acirc", "\u{e2}"), ("atilde", "\u{e3}"),
+    ("auml", "\u{e4}"), ("aring", "\u{e5}"), ("aelig", "\u{e6}"), ("ccedil", "\u{e7}"),
+    ("eacute", "\u{e9}"), ("egrave", "\u{e8}"), ("ecirc", "\u{ea}"), ("euml", "\u{eb}"),
+    ("iacute", "\u{ed}"), ("igrave", "\u{ec}"), ("icirc", "\u{ee}"), ("iuml", "\u{ef}"),
+    ("ntilde", "\u{f1}"), ("oacute", "\u{f3}"), ("ograve", "\u{f2}"), ("ocirc", "\u{f4}"),
+    ("otilde", "\u{f5}"), ("ouml", "\u{f6}"), ("oslash", "\u{f8}"), ("uacute", "\u{fa}"),
+    ("ugrave", "\u{f9}"), ("ucirc", "\u{fb}"), ("uuml", "\u{fc}"), ("yacute", "\u{fd}"),
+    ("yuml", "\u{ff}"), ("oelig", "\u{153}"), ("euro", "\u{20ac}"),
+];
+
+/// true if the text contains tags or entities, the texts already converted
+/// must not be converted again as their line breaks would be lost
+pub fn is_html(s: &str) -> bool {
+    let tag = s.match_indices('<').any(|(i, _)| {
+        s[i + 1..].chars().next().map_or(false, |c| c.is_alphabetic() || c == '/' || c == '!')
+    });
+    tag || s.match_indices('&').any(|(i, _)| entity(&s[i..]).is_some())
+}
+
+/// the character of the entity starting `s`, and the length of the entity
+fn entity(s: &str) -> Option<(String, usize)> {
+    let end = match s.find(';') {
+        Some(end) if end > 1 && end <= 10 => end,
+        _ => return None,
+    };
+    let name = &s[1..end];
+    let decoded = if name.starts_with("#x") || name.starts_with("#X") {
+        u32::from_str_radix(&name[2..], 16).ok().and_then(char::from_u32).map(|c| c.to_string())
+    } else if name.starts_with('#') {
+        name[1..].parse::<u32>().ok().and_then(char::from_u32).map(|c| c.to_string())
+    } else {
+        match ENTITIES.iter().find(|&&(n, _)| n == name) {
+            Some(&(_, v)) => Some(v.to_string()),
+            // &Eacute; is the uppercase of &eacute;
+            None => {
+                let lower = name.to_lowercase();
+                ENTITIES.iter()
+                    .find(|&&(n, _)| n == lower && n.len() > 4)
+                    .map(|&(_, v)| v.to_uppercase())
+            },
+        }
+    };
+    decoded.map(|d| (d, end + 1))
+}
+
+/// replace the entities of `s` by their characters
+pub fn decode(s: &str) -> String {
+    let mut out = String::with_capacity(s.len());
+    let mut rest = s;
+    loop {
+        match rest.find('&') {
+            Some(i) => {
+                out.push_str(&rest[..i]);
+                rest = &rest[i..];
+                match entity(rest) {
+                    Some((c, len)) => {
+                        out.push_str(&*c);
+                        rest = &rest[len..];
+                    },
+                    None => {
+                        out.push('&');
+                        rest = &rest[1..];
+                    },
+                }
+            },
+            None => {
+                out.push_str(rest);
+                return out
+            },
+        }
+    }
+}
+
+fn escape(s: &str) -> String {
+    s.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+}
+
+/// a tag of the html
+enum Token<'a> {
+    Text(&'a str),
+    /// name in lowercase, true for a closing tag, and the whole tag
+    Tag(String, bool, &'a str),
+}
+
+/// split the html in texts and tags, the comments are dropped
+fn tokens(html: &str) -> Vec<Token> {
+    let mut tokens = vec![];
+    let mut rest = html;
+    while !rest.is_empty() {
+        let start = match rest.find('<') {
+            Some(i) => i,
+            None => {
+                tokens.push(Token::Text(rest));
+                break
+            },
+        };
+        if start > 0 {
+            tokens.push(Token::Text(&rest[..start]));
+            rest = &rest[start..];
+        }
+
+        if rest.starts_with("<!--") {
+            rest = match rest.find("-->") {
+                Some(end) => &rest[end + 3..],
+                None => "",
+            };
+            continue
+        }
+        let is_tag = rest[1..].chars().next().map_or(false, |c| c.is_alphabetic() || c == '/' || c == '!');
+        match (is_tag, rest.find('>')) {
+            (true, Some(end)) => {
+                let inner = &rest[1..end];
+                let closing = inner.starts_with('/');
+                let name = inner.trim_left_matches('/')
+                    .chars()
+                    .take_while(|c| c.is_alphanumeric())
+                    .collect::<String>()
+                    .to_lowercase();
+                tokens.push(Token::Tag(name, closing, inner));
+                rest = &rest[end + 1..];
+            },
+            // a lone <, part of the text
+            _ => {
+                tokens.push(Token::Text(&rest[..1]));
+                rest = &rest[1..];
+            },
+        }
+    }
+    tokens
+}
+
+/// remove the spaces ending the text
+fn trim_end(out: &mut String) {
+    let len = out.trim_right_matches(' ').len();
+    out.truncate(len);
+}
+
+/// end the text with at least `n` line breaks, unless it is empty
+fn line_breaks(out: &mut String, n: usize) {
+    trim_end(out);
+    let existing = out.len() - out.trim_right_matches('\n').len();
+    if out.len() > existing {
+        for _ in existing..n {
+            out.push('\n');
+        }
+    }
+}
+
+/// true for the tags starting or ending a script or a style, updating
+/// `skipped` which holds the tag being skipped
+fn skip(skipped: &mut Option<String>, name: &str, closing: bool) -> bool {
+    match *skipped {
+        Some(ref s) if closing && s == name => {},
+        Some(_) => return true,
+        None if SKIPPED_TAGS.contains(&name) => {
+            if !closing {
+                *skipped = Some(name.to_string());
+            }
+            return true
+        },
+        None => return false,
+    }
+    *skipped = None;
+    true
+}
+
+/// the text of the html, with a blank line between paragraphs, a line
+/// break for each `<br>` and list item, and the whitespaces collapsed
+pub fn to_text(html: &str) -> String {
+    let mut out = String::with_capacity(html.len());
+    let mut skipped: Option<String> = None;
+    let mut lists = 0;
+
+    for t in tokens(html) {
+        match t {
+            Token::Tag(name, closing, _) => {
+                if skip(&mut skipped, &*name, closing) {
+                    continue
+                }
+                if name == "ul" || name == "ol" {
+                    if closing {
+                        lists -= 1;
+                    }
+                    // a nested list starts on the next line of its item
+                    line_breaks(&mut out, if lists > 0 { 1 } else { 2 });
+                    if !closing {
+                        lists += 1;
+                    }
+                } else if name == "br" {
+                    trim_end(&mut out);
+                    if !out.is_empty() && !out.ends_with("\n\n") {
+                        out.push('\n');
+                    }
+                } else if BLOCK_TAGS.contains(&&*name) && lists > 0 {
+                    // the paragraphs of a list item stay in the item, on
+                    // their own line
+                    let empty_item = out[out.rfind('\n').map_or(0, |i| i + 1)..].trim() == "-";
+                    if !empty_item {
+                        line_breaks(&mut out, 1);
+                    }
+                } else if BLOCK_TAGS.contains(&&*name) {
+                    line_breaks(&mut out, 2);
+                } else if LINE_TAGS.contains(&&*name) && !closing {
+                    line_breaks(&mut out, 1);
+                    if name == "li" {
+                        out.push_str("- ");
+                    }
+                }
+            },
+            Token::Text(s) => {
+                if skipped.is_some() {
+                    continue
+                }
+                for c in decode(s).chars() {
+                    if !c.is_whitespace() {
+                        out.push(c);
+                    } else if out.chars().last().map_or(false, |l| !l.is_whitespace()) {
+                        out.push(' ');
+                    }
+                }
+            },
+        }
+    }
+
+    out.lines()
+        .map(|l| l.trim())
+        .collect::<Vec<_>>()
+        .join("\n")
+        .trim()
+        .to_string()
+}
+
+/// the value of an attribute of a tag, `inner` being the tag without its
+/// angle brackets. the attributes are read one by one, so a name is only
+/// found whole and never in the value of another attribute
+fn attribute(inner: &str, name: &str) -> Option<String> {
+    let is_space = |c: char| c.is_whitespace() || c == '/';
+    // after the tag name
+    let mut rest = inner.trim_left_matches(|c: char| !is_space(c));
+    loop {
+        rest = rest.trim_left_matches(is_space);
+        if rest.is_empty() {
+            return None
+        }
+        let end = rest.find(|c: char| is_space(c) || c == '=').unwrap_or(rest.len());
+        let found = rest[..end].to_lowercase() == name;
+        rest = rest[end..].trim_left();
+        if !rest.starts_with('=') {
+            // an attribute without value
+            if found {
+                return Some(String::new())
+            }
+            continue
+        }
+        rest = rest[1..].trim_left();
+        let value = match rest.chars().next() {
+            Some(q) if q == '"' || q == '\'' => {
+                let value = rest[1..].split(q).next().unwrap_or("");
+                rest = &rest[cmp::min(value.len() + 2, rest.len())..];
+                value
+            },
+            _ => {
+                let value = rest.split(char::is_whitespace).next().unwrap_or("");
+                rest = &rest[value.len()..];
+                value
+            },
+        };
+        if found {
+            return Some(decode(value))
+        }
+    }
+}
+
+/// true for the links kept, the http ones whatever the case of the scheme
+fn http_link(href: &str) -> bool {
+    let lower = href.to_lowercase();
+    lower.starts_with("http://") || lower.starts_with("https://")
+}
+
+/// the html with only the formatting and link tags, without attributes
+/// except the http links. the tags are balanced, the closing tags never
+/// opened are dropped and the ones missing are added
+pub fn sanitize(html: &str) -> String {
+    let mut out = String::with_capacity(html.len());
+    let mut skipped: Option<String> = None;
+    // the tags written and not closed yet
+    let mut open: Vec<String> = vec![];
+
+    for t in tokens(html) {
+        match t {
+            Token::Tag(name, closing, inner) => {
+                if skip(&mut skipped, &*name, closing) {
+                    continue
+                }
+                if !SAFE_TAGS.contains(&&*name) {
+                    continue
+                } else if closing {
+                    // also close the tags opened inside this one
+                    match open.iter().rposition(|o| *o == name) {
+                        Some(i) => for o in open.drain(i..).rev() {
+                            out.push_str(&*format!("</{}>", o));
+                        },
+                        None => {},
+                    }
+                } else if name == "br" {
+                    out.push_str("<br>");
+                } else {
+                    // a new item or paragraph ends the previous one
+                    if (name == "li" || name == "p") && open.last() == Some(&name) {
+                        open.pop();
+                        out.push_str(&*format!("</{}>", name));
+                    }
+                    match attribute(inner, "href") {
+                        Some(ref href) if name == "a" && http_link(href) => {
+                            out.push_str(&*format!("<a href=\"{}\">", escape(href)));
+                        },
+                        _ => out.push_str(&*format!("<{}>", name)),
+                    }
+                    open.push(name);
+                }
+            },
+            Token::Text(s) => {
+                if skipped.is_none() {
+                    out.push_str(&*escape(&*decode(s)));
+                }
+            },
+        }
+    }
+    for o in open.into_iter().rev() {
+        out.push_str(&*format!("</{}>", o));
+    }
+    out.trim().to_string()
+}
+
+/// true if the text ends with a `<br>` written by `to_markdown`, a newline
+/// after an odd number of backslashes
+fn ends_with_hard_break(out: &str) -> bool {
+    out.ends_with('\n') && out[..out.len() - 1].chars().rev().take_while(|&c| c == '\\').count() % 2 == 1
+}
+
+/// `line_breaks` for markdown, a hard break before a paragraph break
+/// would be written as a backslash
+fn markdown_breaks(out: &mut String, n: usize) {
+    if ends_with_hard_break(out) {
+        let len = out.len() - 2;
+        out.truncate(len);
+    }
+    line_breaks(out, n);
+}
+
+/// escape the characters of a text which markdown would read as syntax
+fn push_markdown_char(out: &mut String, c: char) {
+    let escaped = {
+        let line = out[out.rfind('\n').map_or(0, |i| i + 1)..].trim();
+        match c {
+            '\\' | '`' | '*' | '_' | '[' | ']' | '<' | '>' | '#' => true,
+            '-' | '+' => line.is_empty(),
+            // 1994. starting a line would start a list
+            '.' | ')' => !line.is_empty() && line.chars().all(|c| c.is_digit(10)),
+            _ => false,
+        }
+    };
+    if escaped {
+        out.push('\\');
+    }
+    out.push(c);
+}
+
+/// the markdown marker of the emphasis tags
+fn emphasis(name: &str) -> Option<&'static str> {
+    match name {
+        "em" | "i" => Some("*"),
+        "strong" | "b" => Some("**"),
+        _ => None,
+    }
+}
+
+/// an emphasis or a link being written: its opening marker, its closing
+/// marker, and the length of the text once the opening marker was written
+type Marker = (&'static str, String, usize);
+
+/// the number of markers, from the last one, with nothing written after
+/// them
+fn just_opened(out: &str, open: &[Marker]) -> usize {
+    let mut len = out.len();
+    let mut n = 0;
+    for &(m, _, end) in open.iter().rev() {
+        if end != len || !out[..len].ends_with(m) {
+            break
+        }
+        len -= m.len();
+        n += 1;
+    }
+    n
+}
+
+/// close the markers opened from `i`, the last one first. a marker with
+/// nothing after it is removed, and the spaces before a closing marker are
+/// moved after it as markdown does not allow them
+fn close_from(out: &mut String, open: &mut Vec<Marker>, i: usize) {
+    while open.len() > i {
+        let (m, closer, end) = open.pop().unwrap();
+        if out.len() == end && out.ends_with(m) {
+            let len = out.len() - m.len();
+            out.truncate(len);
+            continue
+        }
+        let len = out.len();
+        trim_end(out);
+        let trimmed = out.len() < len;
+        out.push_str(&*closer);
+        if trimmed {
+            out.push(' ');
+        }
+    }
+}
+
+/// close the last marker `m` opened, nothing if there is none
+fn close(out: &mut String, open: &mut Vec<Marker>, m: &str) {
+    match open.iter().rposition(|&(o, _, _)| o == m) {
+        Some(i) => close_from(out, open, i),
+        None => {},
+    }
+}
+
+/// end the line with a hard break, unless it is empty
+fn hard_break(out: &mut String) {
+    trim_end(out);
+    if ends_with_hard_break(out) {
+        markdown_breaks(out, 2);
+    } else if !out.is_empty() && !out.ends_with('\n') {
+        out.push_str("\\\n");
+    }
+}
+
+/// the html as commonmark, with its paragraphs, lists, emphasis and http
+/// links
+pub fn to_markdown(html: &str) -> String {
+    let mut out = String::with_capacity(html.len());
+    let mut skipped: Option<String> = None;
+    // the lists being written, ordered or not, with their last item number
+    // and the width of its marker
+    let mut lists: Vec<(bool, usize, usize)> = vec![];
+    // the length of the text after the marker of the last item
+    let mut item = 0;
+    // for each link being written, true if it is kept
+    let mut links: Vec<bool> = vec![];
+    let mut open: Vec<Marker> = vec![];
+
+    for t in tokens(html) {
+        match t {
+            Token::Tag(name, closing, inner) => {
+                if skip(&mut skipped, &*name, closing) {
+                    continue
+                }
+                match &*name {
+                    "br" => hard_break(&mut out),
+                    "ul" | "ol" => {
+                        if closing {
+                            lists.pop();
+                        }
+                        // a nested list starts on the next line of its item
+                        markdown_breaks(&mut out, if lists.is_empty() { 2 } else { 1 });
+                        if !closing {
+                            lists.push((name == "ol", 0, 0));
+                        }
+                    },
+                    "li" if !closing => {
+                        markdown_breaks(&mut out, 1);
+                        // the items of a nested list are indented by the
+                        // markers of their parents
+                        let indent = lists.iter().rev().skip(1).map(|&(_, _, w)| w).sum::<usize>();
+                        let marker = match lists.last_mut() {
+                            Some(&mut (true, ref mut n, ref mut w)) => {
+                                *n += 1;
+                                let m = format!("{}. ", n);
+                                *w = m.len();
+                                m
+                            },
+                            Some(&mut (false, _, ref mut w)) => {
+                                *w = 2;
+                                "- ".to_string()
+                            },
+                            None => "- ".to_string(),
+                        };
+                        out.push_str(&*" ".repeat(indent));
+                        out.push_str(&*marker);
+                        item = out.len();
+                    },
+                    "h1" | "h2" | "h3" | "h4" | "h5" | "h6" => {
+                        markdown_breaks(&mut out, 2);
+                        if !closing {
+                            out.push_str(&*format!("{} ", "#".repeat(name[1..].parse().unwrap_or(1))));
+                        }
+                    },
+                    "a" if !closing => {
+                        match attribute(inner, "href") {
+                            Some(ref href) if http_link(href) => {
+                                let href = href.replace(' ', "%20").replace('(', "%28").replace(')', "%29");
+                                out.push('[');
+                                open.push(("[", format!("]({})", href), out.len()));
+                                links.push(true);
+                            },
+                            _ => links.push(false),
+                        }
+                    },
+                    "a" => {
+                        if links.pop() == Some(true) {
+                            close(&mut out, &mut open, "[");
+                        }
+                    },
+                    _ => {
+                        match emphasis(&*name) {
+                            Some(m) if closing => close(&mut out, &mut open, m),
+                            Some(m) => {
+                                out.push_str(m);
+                                open.push((m, m.to_string(), out.len()));
+                            },
+                            // the paragraphs of a list item stay in the
+                            // item, on their own line
+                            None if BLOCK_TAGS.contains(&&*name) && !lists.is_empty() => {
+                                if out.len() > item && !ends_with_hard_break(&out) {
+                                    hard_break(&mut out);
+                                }
+                            },
+                            None if BLOCK_TAGS.contains(&&*name) => markdown_breaks(&mut out, 2),
+                            None if LINE_TAGS.contains(&&*name) && !closing => hard_break(&mut out),
+                            None => {},
+                        }
+                    },
+                }
+            },
+            Token::Text(s) => {
+                if skipped.is_some() {
+                    continue
+                }
+                for c in decode(s).chars() {
+                    if !c.is_whitespace() {
+                        push_markdown_char(&mut out, c);
+                        continue
+                    }
+                    // the markers just opened are moved after the space,
+                    // "a <em> b" is "a *b"
+                    let n = just_opened(&out, &open);
+                    let first = open.len() - n;
+                    let len = out.len() - open[first..].iter().map(|&(m, _, _)| m.len()).sum::<usize>();
+                    out.truncate(len);
+                    if out.chars().last().map_or(false, |l| !l.is_whitespace()) {
+                        out.push(' ');
+                    }
+                    for &mut (m, _, ref mut end) in open[first..].iter_mut() {
+                        out.push_str(m);
+                        *end = out.len();
+                    }
+                }
+            },
+        }
+    }
+
+    close_from(&mut out, &mut open, 0);
+    markdown_breaks(&mut out, 0);
+    out.lines()
+        .map(|l| l.trim_right())
+        .collect::<Vec<_>>()
+        .join("\n")
+        .trim()
+        .to_string()
+}
+
+/// a plain text as commonmark, its syntax characters escaped, with its
+/// blank lines kept as paragraph breaks and its other line breaks as hard
+/// breaks
+pub fn text_to_markdown(text: &str) -> String {
+    let mut out = String::with_capacity(text.len());
+    for line in text.trim().lines().map(|l| l.trim()) {
+        if line.is_empty() {
+            markdown_breaks(&mut out, 2);
+            continue
+        }
+        hard_break(&mut out);
+        for c in line.chars() {
+            push_markdown_char(&mut out, c);
+        }
+    }
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn detect_html() {
+        let cases = [
+            ("plain text", false),
+            ("a < b and 5<6", false),
+            ("AT&T; a & b", false),
+            ("a <b>bold</b>", true),
+            ("</p>", true),
+            ("<!-- comment -->", true),
+            ("a &amp; b", true),
+            ("&#233;", true),
+        ];
+        for &(s, expected) in cases.iter() {
+            assert_eq!(is_html(s), expected, "{}", s);
+        }
+    }
+
+    #[test]
+    fn attributes() {
+        let cases = [
+            ("a href=\"http://e.com/\"", Some("http://e.com/")),
+            ("a HREF='x' title=y", Some("x")),
+            ("a href = x", Some("x")),
+            ("a href=http://e.com/a/b", Some("http://e.com/a/b")),
+            ("a href=\"a &amp; b\"", Some("a & b")),
+            ("a data-href=\"x\"", None),
+            ("a data-href=\"x\" href=\"y\"", Some("y")),
+            ("a title=\"href=x\"", None),
+            ("a href", Some("")),
+            // lowercased, the dotted capital i is longer
+            ("a title=\"\u{130}\u{130}\u{130}\" href=\"\u{e9}x\"", Some("\u{e9}x")),
+            ("a \u{130}\u{130}href=\"x\"", None),
+            ("a title=\"unclosed", None),
+        ];
+        for &(inner, expected) in cases.iter() {
+            assert_eq!(attribute(inner, "href").as_ref().map(|s| &**s), expected, "{}", inner);
+        }
+    }
+
+    #[test]
+    fn text() {
+        let cases = [
+            ("<p>one</p><p>two</p>", "one\n\ntwo"),
+            ("a<br>b<br/><br>c", "a\nb\n\nc"),
+            ("  lots \n of\t spaces  ", "lots of spaces"),
+            ("<ul><li>one<ul><li>nested</li></ul></li><li>two</li></ul>after",
+             "- one\n- nested\n- two\n\nafter"),
+            ("<ul><li><p>a</p><p>b</p></li></ul>", "- a\nb"),
+            ("a &amp; b &eacute;&Eacute;&#233;&#xE9; &bogus; &", "a & b \u{e9}\u{c9}\u{e9}\u{e9} &bogus; &"),
+            ("<script>x<p>y</p></script><style>p{}</style>z", "z"),
+            ("<!-- c -->x", "x"),
+            ("a < b", "a < b"),
+            ("Title<h2>x</h2>", "Title\n\nx"),
+            ("<br><p></p>text<br>", "text"),
+            // unbalanced tags
+            ("x</em>y<b>z", "xyz"),
+            ("<p>one<p>two</div>three", "one\n\ntwo\n\nthree"),
+        ];
+        for &(html, expected) in cases.iter() {
+            assert_eq!(to_text(html), expected, "{}", html);
+        }
+    }
+
+    #[test]
+    fn sanitized() {
+        let cases = [
+            ("<p>one</p><p>two</p>", "<p>one</p><p>two</p>"),
+            ("a<br>b<br/></br>c", "a<br>b<br>c"),
+            ("<p class=\"x\" style=\"color:red\">t</p><img src=x onerror=y>", "<p>t</p>"),
+            ("<script>alert(1)</script><style>p{}</style>z", "z"),
+            ("<a href=\"javascript:alert(1)\" onclick=\"x\">bad</a>", "<a>bad</a>"),
+            ("<a href='HTTPS://e.com/?a=1&amp;b=\"2\"'>ok</a>",
+             "<a href=\"HTTPS://e.com/?a=1&amp;b=&quot;2&quot;\">ok</a>"),
+            ("a < b &eacute; &bogus;", "a &lt; b \u{e9} &amp;bogus;"),
+            ("<ul><li>one<ul><li>nested</li></ul></li></ul>", "<ul><li>one<ul><li>nested</li></ul></li></ul>"),
+            // unbalanced tags
+            ("<b>bold <i>both</b> i</i>", "<b>bold <i>both</i></b> i"),
+            ("x</em>y<b>z", "xy<b>z</b>"),
+            ("<ul><li>a<li>b", "<ul><li>a</li><li>b</li></ul>"),
+            ("<p>one<p>two", "<p>one</p><p>two</p>"),
+        ];
+        for &(html, expected) in cases.iter() {
+            assert_eq!(sanitize(html), expected, "{}", html);
+        }
+    }
+
+    #[test]
+    fn markdown() {
+        let cases = [
+            ("<p>one</p><p>two</p>", "one\n\ntwo"),
+            ("a<br>b<br><br>c", "a\\\nb\n\nc"),
+            ("<h2>Title</h2>text", "## Title\n\ntext"),
+            ("a &amp; b &eacute;&#233;", "a & b \u{e9}\u{e9}"),
+            ("1. *a* _b_ [c] #d", "1\\. \\*a\\* \\_b\\_ \\[c\\] \\#d"),
+            ("a <em> b</em> c", "a *b* c"),
+            ("<em><br> x</em>", "*\\\nx*"),
+            ("<em></em>x<b> </b>y", "x y"),
+            ("<a href=\"javascript:x\">bad</a> <a href=\"HTTPS://e.com/a b\">ok</a>",
+             "bad [ok](HTTPS://e.com/a%20b)"),
+            ("<a href=\"http://x/\u{e9}\">x<b></a> y", "[x](http://x/\u{e9}) y"),
+            ("<ul><li>one<ul><li>nested</li></ul></li><li>two</li></ul>", "- one\n  - nested\n- two"),
+            ("<ol><li>a<ol><li>b</li></ol></li></ol>", "1. a\n   1. b"),
+            ("<ul><li><p>a</p><p>b</p></li></ul>", "- a\\\nb"),
+            // unbalanced tags
+            ("x</em>y<b>z", "xy**z**"),
+            ("<b>bold <i>both</b> i</i>", "**bold *both*** i"),
+            ("<p>one<p>two</div>three", "one\n\ntwo\n\nthree"),
+        ];
+        for &(html, expected) in cases.iter() {
+            assert_eq!(to_markdown(html), expected, "{}", html);
+        }
+    }
+
+    #[test]
+    fn plain_markdown() {
+        let cases = [
+            ("  one  ", "one"),
+            ("one\n\n\ntwo", "one\n\ntwo"),
+            ("one\ntwo", "one\\\ntwo"),
+            ("- a *b* [c]\n1. d", "\\- a \\*b\\* \\[c\\]\\\n1\\. d"),
+        ];
+        for &(text, expected) in cases.iter() {
+            assert_eq!(text_to_markdown(text), expected, "{}", text);
+        }
+    }
+}
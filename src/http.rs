@@ -0,0 +1,191 @@
+// Copyright 2017 Jeremy Letang.
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+
+//! Every api request and picture download goes through this module so the
+//! responses can be recorded in a fixtures directory then replayed without
+//! network.
+//!
+//! Fixtures are keyed by the request with its query parameters sorted and
+//! the api key removed, so they can be shared without leaking the key. The
+//! responses which are not text, the pictures, are saved in a `.bin` file
+//! next to their fixture.
+
+use reqwest::{Client, Url};
+use reqwest::header::UserAgent;
+use serde::Deserialize;
+use serde_json;
+use sha2::{Digest, Sha256};
+use std::error::Error;
+use std::fs::{self, File};
+use std::io::{Read, Write};
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+
+/// some apis (wikimedia) reject the requests without a user agent
+const USER_AGENT: &'static str = "whlp/0.1.0 (https://github.com/jeremyletang/whl-puller)";
+
+/// query parameters never saved in the fixtures
+const SECRET_PARAMS: [&'static str; 1] = ["api_key"];
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum Mode {
+    /// send the requests
+    Live,
+    /// send the requests and save the responses in the directory
+    Record(PathBuf),
+    /// never send requests, serve the responses saved in the directory
+    Replay(PathBuf),
+}
+
+lazy_static! {
+    static ref MODE: Mutex<Mode> = Mutex::new(Mode::Live);
+}
+
+pub fn set_mode(mode: Mode) {
+    info!("http mode: {:?}", mode);
+    *MODE.lock().unwrap() = mode;
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+struct Fixture {
+    request: String,
+    status: u16,
+    body: String,
+    /// the body is in the `.bin` file of the fixture
+    #[serde(default)]
+    binary: bool,
+}
+
+pub fn get_json<T: Deserialize>(url: &str, what: &str) -> Result<T, String> {
+    get_body(url, what).and_then(|buf| {
+        // unserialize
+        serde_json::from_str::<T>(&*buf)
+            .map_err(|e| format!("unable to deserialize payload, try again, {}", e))
+    })
+}
+
+pub fn get_body(url: &str, what: &str) -> Result<String, String> {
+    get_bytes(url, what).and_then(|buf| {
+        String::from_utf8(buf)
+            .map_err(|e| format!("unable to read http request payload, try again, {}", e))
+    })
+}
+
+pub fn get_bytes(url: &str, what: &str) -> Result<Vec<u8>, String> {
+    let mode = MODE.lock().unwrap().clone();
+    let (status, body) = match mode {
+        Mode::Live => send(url, what)?,
+        Mode::Record(ref dir) => {
+            let (status, body) = send(url, what)?;
+            record(dir, url, status, &*body)?;
+            (status, body)
+        },
+        Mode::Replay(ref dir) => replay(dir, url),
+    };
+
+    if status >= 200 && status < 300 {
+        Ok(body)
+    } else {
+        Err(format!("unexpected http status, try again"))
+    }
+}
+
+fn send(url: &str, what: &str) -> Result<(u16, Vec<u8>), String> {
+    match Client::new().unwrap().get(url).header(UserAgent(USER_AGENT.into())).send() {
+        Ok(mut r) => {
+            let status = r.status().to_u16();
+            let mut buf = vec![];
+            match r.read_to_end(&mut buf) {
+                Ok(_) => Ok((status, buf)),
+                Err(e) => Err(format!("unable to read http request payload, try again, {}", e))
+            }
+        },
+        Err(e) => Err(format!("unable to get {}, {}", what, e.description()))
+    }
+}
+
+/// the request without its secrets and with sorted query parameters
+pub fn normalize(url: &str) -> String {
+    match Url::parse(url) {
+        Ok(u) => {
+            let mut params = u.query_pairs()
+                .filter(|&(ref k, _)| !SECRET_PARAMS.iter().any(|s| *s == &**k))
+                .map(|(k, v)| format!("{}={}", k, v))
+                .collect::<Vec<_>>();
+            params.sort();
+            format!("GET {}://{}:{}{}?{}",
+                    u.scheme(), u.host_str().unwrap_or(""), u.port_or_known_default().unwrap_or(0),
+                    u.path(), params.join("&"))
+        },
+        Err(_) => format!("GET {}", url),
+    }
+}
+
+fn fixture_path(dir: &Path, request: &str) -> PathBuf {
+    let mut hasher = Sha256::default();
+    hasher.input(request.as_bytes());
+    let hash = hasher.result().iter().map(|b| format!("{:02x}", b)).collect::<String>();
+    dir.join(format!("{}.json", hash))
+}
+
+fn write(path: &Path, buf: &[u8]) -> Result<(), String> {
+    File::create(path)
+        .and_then(|mut f| f.write_all(buf))
+        .map_err(|e| format!("unable to write fixture {}, {}", path.display(), e))
+}
+
+fn record(dir: &Path, url: &str, status: u16, body: &[u8]) -> Result<(), String> {
+    let request = normalize(url);
+    let path = fixture_path(dir, &*request);
+    let text = String::from_utf8(body.to_vec()).ok();
+    let fixture = Fixture {
+        request: request,
+        status: status,
+        binary: text.is_none(),
+        body: text.unwrap_or_default(),
+    };
+    let json = serde_json::to_string_pretty(&fixture)
+        .map_err(|e| format!("unable to serialize fixture, {}", e))?;
+    fs::create_dir_all(dir)
+        .map_err(|e| format!("unable to create directory {}, {}", dir.display(), e))?;
+    if fixture.binary {
+        write(&*path.with_extension("bin"), body)?;
+    }
+    write(&*path, json.as_bytes())?;
+    debug!("recorded {} in {}", fixture.request, path.display());
+    Ok(())
+}
+
+/// a missing fixture is a mistake in the fixtures, not a network error the
+/// callers could ignore, so it aborts
+fn replay(dir: &Path, url: &str) -> (u16, Vec<u8>) {
+    let request = normalize(url);
+    let path = fixture_path(dir, &*request);
+    let mut buf = String::new();
+    match File::open(&path).and_then(|mut f| f.read_to_string(&mut buf)) {
+        Ok(_) => {},
+        Err(e) => panic!("no recorded response for request '{}' ({}): {}",
+                         request, path.display(), e),
+    }
+    match serde_json::from_str::<Fixture>(&*buf) {
+        Ok(ref f) if f.binary => {
+            let bin = path.with_extension("bin");
+            debug!("replaying {} from {}", request, bin.display());
+            let mut body = vec![];
+            match File::open(&bin).and_then(|mut f| f.read_to_end(&mut body)) {
+                Ok(_) => (f.status, body),
+                Err(e) => panic!("no recorded body for request '{}' ({}): {}",
+                                 request, bin.display(), e),
+            }
+        },
+        Ok(f) => {
+            debug!("replaying {} from {}", request, path.display());
+            (f.status, f.body.into_bytes())
+        },
+        Err(e) => panic!("invalid fixture {}, {}", path.display(), e),
+    }
+}
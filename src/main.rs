@@ -10,10 +10,11 @@ extern crate chrono;
 extern crate diesel;
 #[macro_use]
 extern crate diesel_codegen;
-extern crate dotenv;
 extern crate clap;
 extern crate env_logger;
-extern crate hyper;
+extern crate image;
+#[macro_use]
+extern crate lazy_static;
 #[macro_use]
 extern crate log;
 extern crate regex;
@@ -22,68 +23,56 @@ extern crate serde;
 #[macro_use]
 extern crate serde_derive;
 extern crate serde_json;
+extern crate sha2;
 extern crate time;
+extern crate toml;
 extern crate uuid;
 extern crate xml;
 
-use clap::{App, Arg};
-use diesel::migrations;
+use cli::{Command, HarvestOptions, MediaOptions, SyncOptions};
 use diesel::prelude::*;
 use diesel::pg::PgConnection;
-use diesel::result::{Error, DatabaseErrorKind};
+use diesel::result::{Error, DatabaseErrorKind, TransactionError};
 use domain::{Monument, License};
-use regex::Regex;
-use std::io::stdout;
+use domain::attribution::Attribution;
+use report::Report;
+use std::fmt::Display;
 use std::path::Path;
-use std::collections::HashMap;
+use std::collections::{HashMap, HashSet};
 use uuid::Uuid;
 use xml::reader::{XmlEvent, EventReader};
-use flickr_api::FindByLatLonError;
-
+use flickr_api::PhotoInfoError;
+use providers::{CommonsProvider, FixtureProvider, FlickrProvider, PictureProvider, ProviderLicense};
+use providers::flickr::FLICKR;
+
+mod cli;
+mod commons_api;
+mod components;
+mod config;
+mod curation;
+mod dedupe;
+mod derivatives;
 mod domain;
+mod export;
 mod flickr_api;
+mod freshness;
+mod html;
+mod http;
+mod migrate;
+mod mirror;
+mod overrides;
+mod providers;
+mod repair;
+mod report;
+mod scheduler;
+mod stats;
+#[cfg(test)]
+mod test_db;
 mod unesco_xml;
+mod webp;
 
-struct CmdLineArgs {
-    pub pq_addr: String,
-    pub migrations: Option<String>,
-    pub xml: Option<String>,
-    pub flickr_key: Option<String>,
-}
-
-fn parse_cmdline() -> CmdLineArgs {
-    let matches = App::new("whlp")
-        .version("v0.1.0")
-        .global_setting(clap::AppSettings::ColoredHelp)
-        .about("retrieve information from unesco whc and store them in pq")
-        .arg(Arg::with_name("pq-addr")
-             .long("pq-addr")
-             .help("postgres server address")
-             .takes_value(true)
-             .required(true))
-        .arg(Arg::with_name("migrations")
-             .long("migrations")
-             .help("database migrations folder")
-             .takes_value(true))
-        .arg(Arg::with_name("xml")
-             .long("xml")
-             .help("use local whc xml file")
-             .takes_value(true))
-        .arg(Arg::with_name("flickr-key")
-             .long("flickr-key")
-             .help("flicker api key to list pictures of the monuments")
-             .takes_value(true))
-        .get_matches();
-
-    CmdLineArgs {
-        pq_addr: matches.value_of("pq-addr").unwrap().into(),
-        migrations: matches.value_of("migrations").map_or(None, |s| Some(s.into())),
-        xml: matches.value_of("xml").map_or(None, |s| Some(s.into())),
-        flickr_key: matches.value_of("flickr-key").map_or(None, |s| Some(s.into())),
-    }
-}
-
-fn read_xml(xml: &str) -> Vec<Monument> {
+/// the monuments of the whc xml, their texts converted from html
+fn read_xml(xml: &str, keep_html: bool) -> Vec<Monument> {
     let parser = EventReader::new(xml.as_bytes());
     let mut current_monument = Monument::new();
     let mut monuments = vec![];
@@ -102,6 +91,7 @@ fn read_xml(xml: &str) -> Vec<Monument> {
             Ok(XmlEvent::EndElement { name }) => {
                 if in_row && &*name.local_name == "row".to_string() {
                     in_row = false;
+                    current_monument.clean_html(keep_html);
                     monuments.push(current_monument);
                     current_monument = Monument::new();
                     current_element = String::new();
@@ -126,55 +116,90 @@ pub fn establish_connection(pq_addr: &str) -> PgConnection {
         .expect(&format!("Error connecting to {}", pq_addr))
 }
 
-pub fn run_migrations(conn: &PgConnection, migrations_path: Option<String>) {
-    // if migrations are specified by the user, just run then
-    // or do nothing
-    match migrations_path {
-        Some(m) => {
-            info!("try to find migrations in path: {}", m);
-            match migrations::search_for_migrations_directory(Path::new(&*m)) {
-                Ok(pb) => {
-                    info!("migrations found at: {}", pb.to_str().unwrap());
-                    info!("executing migrations ...");
-                    match migrations::run_pending_migrations_in_directory(conn, pb.as_path(), &mut stdout()) {
-                        Ok(_) => info!("migrations executed with success !"),
-                        Err(e) => panic!(format!("{}", e)),
-                    }
-                },
-                Err(e) => panic!(format!("{}", e)),
-            }
-        },
-        None => {}
+/// the key of a monument in the reports
+fn monument_key(m: &Monument) -> String {
+    m.id_number.map_or(m.id.clone(), |n| n.to_string())
+}
+
+/// number of monuments saved by statement, far below the 65535 parameters
+/// postgres accepts, so the whole list fits in one or two batches
+const SYNC_BATCH_SIZE: usize = 1000;
+
+fn transaction_error<E: Display>(e: TransactionError<E>) -> String {
+    match e {
+        TransactionError::CouldntCreateTransaction(e) => format!("unable to start a transaction, {}", e),
+        TransactionError::UserReturnedError(e) => format!("{}", e),
     }
 }
 
-pub fn insert_monuments(conn: &PgConnection, monuments: &mut Vec<Monument>) {
+/// save the new monuments by batches, each batch in its own transaction.
+/// when called inside a transaction the batches are savepoints, and the
+/// whole import is committed or rolled back by the caller
+pub fn insert_monuments(conn: &PgConnection,
+                        monuments: Vec<Monument>,
+                        committed: bool,
+                        report: &mut Report) -> Result<(), String> {
     use domain::schema::{monuments, last_updates};
-    let mut monuments_inserted = 0;
 
-    for m in monuments.iter_mut() {
+    // the existing monuments and the repeated ones are skipped up front, a
+    // failed insert would abort the transaction
+    let mut existing = domain::dao::list_monuments(conn)
+        .into_iter()
+        .filter_map(|m| m.id_number)
+        .collect::<HashSet<_>>();
+    let mut new = vec![];
+    for m in monuments {
+        match m.id_number {
+            // it could not be found again on the next sync
+            None => warn!("monument without id_number skipped: {}", m.site.clone().unwrap_or_default()),
+            Some(n) => if existing.insert(n) {
+                new.push(m);
+            } else {
+                debug!("monument already exists: {}", n);
+            },
+        }
+    }
+    for m in new.iter_mut() {
         // create monument
         m.id = Uuid::new_v4().to_string();
-        match diesel::insert(m).into(monuments::table).execute(conn) {
+        report.insert("monuments", monument_key(m), m.site.clone().unwrap_or_default());
+        report.insert("last_updates", monument_key(m), "");
+    }
+    if report.dry_run {
+        info!("{} new monuments found", new.len());
+        return Ok(())
+    }
+
+    let batches = (new.len() + SYNC_BATCH_SIZE - 1) / SYNC_BATCH_SIZE;
+    let mut monuments_inserted = 0;
+    for (i, batch) in new.chunks(SYNC_BATCH_SIZE).enumerate() {
+        let updates = batch.iter()
+            .map(|m| domain::LastUpdate::new(&*m.id))
+            .collect::<Vec<_>>();
+        let res = conn.transaction::<_, Error, _>(|| {
+            diesel::insert(batch).into(monuments::table).execute(conn)?;
+            diesel::insert(&updates).into(last_updates::table).execute(conn)?;
+            Ok(())
+        });
+        match res {
             Ok(_) => {
-                debug!("new monument added: {:?}", m);
-                monuments_inserted += 1;
-                let u = domain::LastUpdate::new(&*m.id);
-                let _ = diesel::insert(&u).into(last_updates::table).execute(conn);
-            }
-            Err(e) => match e {
-                Error::DatabaseError(DatabaseErrorKind::UniqueViolation, _) => {
-                    debug!("monument already exists: {:?}", m)
-                },
-                e => panic!(format!("{}", e))
+                monuments_inserted += batch.len();
+                info!("batch {}/{}: {} monuments {}", i + 1, batches, batch.len(),
+                      if committed { "committed" } else { "saved" });
             },
+            Err(e) => return Err(format!("batch {}/{} of monuments rolled back after {} monuments {}, {}",
+                                         i + 1, batches, monuments_inserted,
+                                         if committed { "committed" } else { "saved" },
+                                         transaction_error(e))),
         }
-
     }
+
     info!("{} new monuments saved", monuments_inserted);
+    Ok(())
 }
 
-pub fn insert_licenses(conn: &PgConnection, key: &str) {
+/// save the flickr licenses and return them
+pub fn insert_licenses(conn: &PgConnection, key: &str, report: &mut Report) -> Vec<flickr_api::License> {
     use domain::schema::licenses;
 
     let licenses = match flickr_api::get_licenses(key) {
@@ -182,154 +207,784 @@ pub fn insert_licenses(conn: &PgConnection, key: &str) {
         Err(e) => panic!(format!("{}", e))
     };
 
-    let mut licenses_inserted = 0;
-
-    for rl in licenses {
-        let mut l: License = rl.into();
-        l.id = Uuid::new_v4().to_string();
-        match diesel::insert(&l).into(licenses::table).execute(conn) {
-            Ok(_) => {
-                debug!("new lisense added: {:?}", l);
-                licenses_inserted += 1;
-            }
-            Err(e) => match e {
-                Error::DatabaseError(DatabaseErrorKind::UniqueViolation, _) => {
-                    debug!("license already exists: {:?}", l)
-                },
-                e => panic!(format!("{}", e))
-            },
+    // only the licenses not saved yet, in a single statement
+    let existing = domain::dao::list_licenses(conn)
+        .into_iter()
+        .filter_map(|l| l.flickr_id)
+        .collect::<HashSet<_>>();
+    let new = licenses.iter()
+        .filter(|rl| !existing.contains(&rl.id))
+        .cloned()
+        .map(|rl| {
+            let mut l: License = rl.into();
+            l.id = Uuid::new_v4().to_string();
+            l
+        })
+        .collect::<Vec<_>>();
+    for l in &new {
+        report.insert("licenses", l.name.clone(), l.url.clone().unwrap_or_default());
+    }
+    let licenses_inserted = new.len();
+    if !report.dry_run && !new.is_empty() {
+        match diesel::insert(&new).into(licenses::table).execute(conn) {
+            Ok(_) => debug!("new licenses added: {:?}", new),
+            Err(e) => panic!(format!("{}", e)),
         }
     }
 
     info!("{} new licenses saved", licenses_inserted);
+    licenses
+}
+
+/// id of the license of a fetched picture, saving the license if the
+/// provider did not declare it before. `created` keeps the licenses created
+/// by provider and name, a dry run never finds them in the database
+fn license_id_for(conn: &PgConnection,
+                  provider: &str,
+                  pl: &ProviderLicense,
+                  created: &mut HashMap<(String, String), String>,
+                  report: &mut Report) -> String {
+    use domain::schema::licenses;
+
+    let key = (provider.to_string(), pl.name.clone());
+    match created.get(&key) {
+        Some(id) => return id.clone(),
+        None => {}
+    }
+    match domain::dao::find_license(conn, provider, pl) {
+        Some(l) => l.id,
+        None => {
+            let l = License::new(provider, pl);
+            created.insert(key, l.id.clone());
+            report.insert("licenses", l.name.clone(), l.url.clone().unwrap_or_default());
+            if !report.dry_run {
+                match diesel::insert(&l).into(licenses::table).execute(conn) {
+                    Ok(_) => debug!("new license added: {:?}", l),
+                    Err(e) => panic!(format!("{}", e)),
+                }
+            }
+            l.id
+        }
+    }
 }
 
 pub fn insert_pictures(conn: &PgConnection,
                        monuments: &Vec<Monument>,
-                       key: &str,
-                       licenses: HashMap<i32, String>) {
+                       providers: &[Box<PictureProvider>],
+                       policy: &freshness::Policy,
+                       report: &mut Report) {
     use domain::schema::pictures;
 
     let mut pictures_inserted = 0;
+    let mut licenses_created = HashMap::new();
+    let overrides = domain::dao::list_search_overrides(conn)
+        .into_iter()
+        .map(|o| (o.id_number, o))
+        .collect::<HashMap<_, _>>();
+    let mut components = HashMap::new();
+    for c in domain::dao::list_monument_components(conn) {
+        components.entry(c.monument_id.clone()).or_insert(vec![]).push(c);
+    }
 
-    for m in monuments {
-        let mut u = domain::dao::last_update_by_monument_id(conn, &*m.id).unwrap();
-        if u.need_refresh() {
-            let mut pid: Option<String> = None;
-            // get place id first
-            if m.latitude.is_some() && m.longitude.is_some() {
-                pid = match flickr_api::get_place(key, m.latitude.unwrap(), m.longitude.unwrap()) {
-                    Ok(pid) => Some(pid),
-                    Err(e) => match e {
-                        FindByLatLonError::NoMatchingPlace => None,
-                        FindByLatLonError::RequestError(e) => panic!(format!("{}", e))
+    for (m, mut run) in scheduler::due(conn, domain::TASK_PICTURES, monuments, policy) {
+        scheduler::start(conn, &mut run, report);
+
+        let o = m.id_number.and_then(|n| overrides.get(&n));
+        // the components of a serial nomination are searched at their
+        // own location, the override only replaces the search of the site
+        let mut locations = vec![(m.clone(), o)];
+        match components.get(&m.id) {
+            Some(cs) => for c in cs {
+                locations.push((m.at_component(c), None));
+            },
+            None => {}
+        }
+
+        let found_before = pictures_inserted;
+        let mut errors = vec![];
+        for provider in providers {
+            let mut ids = vec![];
+            for &(ref l, o) in &locations {
+                match provider.search(l, o) {
+                    Ok(found) => ids.extend(found),
+                    Err(e) => {
+                        warn!("unable to search {} pictures of monument {} at {}, {}",
+                              provider.name(), m.id, l.site.clone().unwrap_or_default(), e);
+                        errors.push(format!("{}: {}", provider.name(), e));
+                    }
+                }
+            }
+            ids.sort();
+            ids.dedup();
+
+            for id in ids {
+                // if picture do not exist already and was not rejected
+                if domain::dao::picture_exists(conn, provider.name(), &*id) ||
+                    domain::dao::picture_rejected(conn, provider.name(), &*id) {
+                    continue
+                }
+                let f = match provider.fetch(&*id) {
+                    Ok(f) => f,
+                    Err(e) => {
+                        warn!("unable to get {} picture {}, {}", provider.name(), id, e);
+                        continue
                     }
                 };
+                let mut pic = f.picture;
+                pic.monument_id = m.id.clone();
+                pic.license_id = license_id_for(conn, provider.name(), &f.license, &mut licenses_created, report);
+                report.insert("pictures", picture_key(&pic), pic.title.clone().unwrap_or_default());
+                if report.dry_run {
+                    pictures_inserted += 1;
+                    insert_picture_sizes(conn, &pic, &f.sizes, report);
+                    continue
+                }
+                match diesel::insert(&pic).into(pictures::table).execute(conn) {
+                    Ok(_) => {
+                        debug!("new picture added: {:?}", pic);
+                        pictures_inserted += 1;
+                        insert_picture_sizes(conn, &pic, &f.sizes, report);
+                    },
+                    Err(e) => panic!("unable to save picture: {:?}", e),
+                }
             }
+        }
 
-            match m.site {
-                Some(ref name) => {
-                    match flickr_api::search_photos(key, name.clone(), pid) {
-                        Ok(photos) => {
-                            for p in photos {
-                                // if picture do not exist already
-                                if !domain::dao::picture_exists(conn, &*p.id) {
-                                    let pi = flickr_api::get_photo_info(key, &*p.id)
-                                        .expect("unable to get photo");
-                                    let u = format!("https://farm{}.staticflickr.com/{}/{}_{}_o.jpg",
-                                                    p.farm, p.server, p.id, pi.originalsecret);
-                                    let pic = domain::Picture::new(
-                                        pi.id,
-                                        m.id.clone(),
-                                        licenses.get(&pi.license).unwrap().clone(),
-                                        pi.owner.username,
-                                        u
-                                    );
-                                    match diesel::insert(&pic).into(pictures::table).execute(conn) {
-                                        Ok(_) => {
-                                            debug!("new picture added: {:?}", pic);
-                                            pictures_inserted += 1;
-                                        },
-                                        Err(e) => panic!("unable to save picture: {:?}", e),
-                                    }
-                                }
-                            }
-                        },
-                        Err(e) => panic!(format!("{}", e))
+        // the run only succeeds if every provider answered, otherwise
+        // the monument is still due on the next run
+        if errors.is_empty() {
+            let found = pictures_inserted - found_before;
+            scheduler::succeed(conn, &mut run, found, report);
+            // last_updates is still read by the clients
+            match domain::dao::last_update_by_monument_id(conn, &*m.id) {
+                Some(mut u) => {
+                    u.set_fresh(found);
+                    report.update("last_updates", monument_key(m), "refreshed");
+                    if !report.dry_run {
+                        domain::dao::update_last_update(conn, &u);
                     }
                 },
-                None => {/* cannot search pictures if no name */}
+                None => {}
             }
-            // insert this monument in the cash
-            u.set_fresh();
-            let _ = domain::dao::update_last_update(conn, &u);
         } else {
-            info!("monument {} have been recently updated, skip it.", m.id);
+            scheduler::fail(conn, &mut run, errors.join("; "), report);
         }
     }
 
     info!("{} new pictures saved", pictures_inserted);
 }
 
-fn remove_html_tags(conn: &PgConnection, monuments: &mut Vec<Monument>) {
-    use std::borrow::Borrow;
-    let re = Regex::new("<[^>]*>").unwrap();
+/// the key of a picture in the reports
+fn picture_key(pic: &domain::Picture) -> String {
+    format!("{}:{}", pic.provider, pic.external_id)
+}
+
+pub fn insert_picture_sizes(conn: &PgConnection,
+                            pic: &domain::Picture,
+                            sizes: &[providers::Size],
+                            report: &mut Report) {
+    use domain::schema::picture_sizes;
+
+    for s in sizes {
+        let ps = domain::PictureSize::new(&*pic.id, s);
+        if report.dry_run {
+            report.insert("picture_sizes", picture_key(pic), &*ps.label);
+            continue
+        }
+        match diesel::insert(&ps).into(picture_sizes::table).execute(conn) {
+            Ok(_) => {
+                debug!("new picture size added: {:?}", ps);
+                report.insert("picture_sizes", picture_key(pic), &*ps.label);
+            },
+            Err(e) => match e {
+                Error::DatabaseError(DatabaseErrorKind::UniqueViolation, _) => {
+                    debug!("picture size already exists: {:?}", ps)
+                },
+                e => panic!(format!("{}", e))
+            },
+        }
+    }
+}
 
-    fn remove_tags(re: &Regex, str: &Option<String>) -> Option<String> {
-        match *str {
-            Some(ref s) => {
-                let res = re.replace_all(s, "");
-                let bres: &str = res.borrow();
-                Some(bres.to_string())
+/// fill the metadata of the pictures saved before they were collected
+pub fn backfill_pictures(conn: &PgConnection, key: &str, report: &mut Report) {
+    let pictures = domain::dao::list_pictures_without_metadata(conn);
+    let mut pictures_updated = 0;
+
+    info!("{} pictures without metadata", pictures.len());
+    for mut pic in pictures {
+        // the photos which are gone are marked so they are not looked up
+        // again, the request errors are retried at the next run
+        let gone = match flickr_api::get_photo_status(key, &*pic.external_id) {
+            Ok(pi) => { pic.set_info(&pi); None },
+            Err(PhotoInfoError::NotFound) => Some((domain::PICTURE_DELETED, "photo not found on flickr")),
+            Err(PhotoInfoError::PermissionDenied) => Some((domain::PICTURE_PRIVATE, "photo is not public anymore")),
+            Err(PhotoInfoError::RequestError(e)) => {
+                warn!("unable to get info of photo {}, {}", pic.external_id, e);
+                continue
+            }
+        };
+        match gone {
+            Some((status, reason)) => {
+                info!("picture {} is {}: {}", pic.id, status, reason);
+                pic.set_status(status, reason);
+                report.update("pictures", picture_key(&pic), format!("status {}: {}", status, reason));
+                if !report.dry_run {
+                    domain::dao::update_picture_status(conn, &pic).unwrap();
+                }
+                continue
             },
-            None => {None}
+            None => {}
         }
+        match flickr_api::get_sizes(key, &*pic.external_id) {
+            Ok(sizes) => {
+                pic.set_original_size(&sizes);
+                let sizes = sizes.into_iter().map(From::from).collect::<Vec<_>>();
+                insert_picture_sizes(conn, &pic, &sizes, report);
+            },
+            Err(e) => warn!("unable to get sizes of photo {}, {}", pic.external_id, e),
+        }
+        report.update("pictures", picture_key(&pic), "metadata");
+        if !report.dry_run {
+            domain::dao::update_picture_metadata(conn, &pic);
+        }
+        debug!("picture metadata updated: {:?}", pic);
+        pictures_updated += 1;
     }
 
+    info!("{} pictures updated", pictures_updated);
+}
+
+/// download the picture sizes with `workers` downloads at the same time,
+/// the database is only written from this thread
+pub fn mirror_pictures(conn: &PgConnection, dir: &str, labels: &[String], workers: usize) {
+    use std::sync::{Arc, Mutex};
+    use std::thread;
+
+    let root = Path::new(dir);
+    let mut files_mirrored = 0;
+
+    // skip the download if the file is already in the mirror
+    let missing = domain::dao::list_picture_sizes_by_labels(conn, labels)
+        .into_iter()
+        .filter(|ps| match (ps.sha256.clone(), ps.mime_type.clone()) {
+            (Some(ref h), Some(ref mt)) if mirror::path_for(root, h, mt).exists() => {
+                debug!("picture size {} already mirrored", ps.id);
+                false
+            },
+            _ => true,
+        })
+        .collect::<Vec<_>>();
+
+    let queue = Arc::new(Mutex::new(missing));
+    let (tx, rx) = ::std::sync::mpsc::channel();
+    for _ in 0..workers {
+        let queue = queue.clone();
+        let tx = tx.clone();
+        let root = root.to_path_buf();
+        thread::spawn(move || {
+            loop {
+                let ps = match queue.lock().unwrap().pop() {
+                    Some(ps) => ps,
+                    None => return,
+                };
+                let res = mirror::mirror(&*root, &*ps.url);
+                if tx.send((ps, res)).is_err() {
+                    return
+                }
+            }
+        });
+    }
+    drop(tx);
+
+    for (mut ps, res) in rx {
+        match res {
+            Ok(f) => {
+                ps.set_mirrored(&f);
+                domain::dao::update_picture_size_file(conn, &ps);
+                files_mirrored += 1;
+            },
+            Err(e) => warn!("unable to mirror picture size {}, {}", ps.id, e),
+        }
+    }
+
+    info!("{} pictures mirrored", files_mirrored);
+}
+
+pub fn generate_derivatives(conn: &PgConnection, dir: &str, profile: &derivatives::Profile) {
+    use domain::schema::picture_derivatives;
+    use std::fs::File;
+    use std::io::Read;
+
+    let root = Path::new(dir);
+    let mut derivatives_generated = 0;
+
+    for mut pic in domain::dao::list_pictures(conn) {
+        let missing = profile.derivatives.iter()
+            .filter(|d| !domain::dao::derivative_exists(conn, &*pic.id, &*d.name))
+            .collect::<Vec<_>>();
+        let need_placeholder = profile.placeholder && pic.blurhash.is_none();
+        if missing.is_empty() && !need_placeholder {
+            continue
+        }
+
+        // use the largest rendition available locally as source
+        let source = domain::dao::list_mirrored_picture_sizes(conn, &*pic.id)
+            .into_iter()
+            .max_by_key(|ps| ps.width);
+        let source = match source {
+            Some(ps) => mirror::path_for(root,
+                                         &*ps.sha256.unwrap(),
+                                         &*ps.mime_type.unwrap()),
+            None => {
+                debug!("picture {} is not mirrored, skip it", pic.id);
+                continue
+            }
+        };
+        let mut buf = vec![];
+        let img = match File::open(&source)
+            .and_then(|mut f| f.read_to_end(&mut buf))
+            .map_err(|e| format!("{}", e))
+            .and_then(|_| derivatives::load(&buf)) {
+            Ok(img) => img,
+            Err(e) => {
+                warn!("unable to load {}, {}", source.display(), e);
+                continue
+            }
+        };
+
+        for d in missing {
+            let f = derivatives::render(&img, d)
+                .and_then(|r| mirror::store(root, &r.buf).map(|f| (r, f)));
+            match f {
+                Ok((r, f)) => {
+                    let pd = domain::PictureDerivative::new(&*pic.id, &*d.name, r.width, r.height, &f);
+                    match diesel::insert(&pd).into(picture_derivatives::table).execute(conn) {
+                        Ok(_) => {
+                            debug!("new picture derivative added: {:?}", pd);
+                            derivatives_generated += 1;
+                        },
+                        Err(e) => panic!("unable to save picture derivative: {:?}", e),
+                    }
+                },
+                Err(e) => warn!("unable to generate {} for picture {}, {}", d.name, pic.id, e),
+            }
+        }
+
+        if need_placeholder {
+            pic.blurhash = Some(derivatives::blurhash(&img));
+            pic.dominant_color = Some(derivatives::dominant_color(&img));
+            domain::dao::update_picture_placeholder(conn, &pic);
+        }
+    }
+
+    info!("{} picture derivatives generated", derivatives_generated);
+}
+
+/// report the derivatives `generate_derivatives` would create, for the
+/// pictures with a rendition mirrored or to mirror
+fn report_derivatives(conn: &PgConnection,
+                      profile: &derivatives::Profile,
+                      labels: &[String],
+                      report: &mut Report) {
+    let sources = domain::dao::list_picture_sizes_by_labels(conn, labels)
+        .into_iter()
+        .map(|ps| ps.picture_id)
+        .collect::<HashSet<_>>();
+
+    for pic in domain::dao::list_pictures(conn).into_iter().filter(|p| sources.contains(&p.id)) {
+        for d in &profile.derivatives {
+            if !domain::dao::derivative_exists(conn, &*pic.id, &*d.name) {
+                report.insert("picture_derivatives", picture_key(&pic), d.name.clone());
+            }
+        }
+        if profile.placeholder && pic.blurhash.is_none() {
+            report.update("pictures", picture_key(&pic), "placeholder");
+        }
+    }
+}
+
+/// query flickr again for each active picture to find the ones which
+/// have been deleted, made private or moved to a non free license
+pub fn verify_pictures(conn: &PgConnection,
+                       key: &str,
+                       licenses: &HashMap<i32, String>,
+                       harvested: &[i32],
+                       purge: bool,
+                       report: &mut Report) {
+    let mut pictures_changed = 0;
+
+    for mut pic in domain::dao::list_pictures_by_status(conn, FLICKR, domain::PICTURE_ACTIVE) {
+        pic.set_verified();
+        match flickr_api::get_photo_status(key, &*pic.external_id) {
+            Ok(pi) => {
+                let current = licenses.iter()
+                    .find(|&(_, id)| *id == pic.license_id)
+                    .map(|(fid, _)| *fid);
+                if current != Some(pi.license) {
+                    let reason = format!("license changed from {} to {}",
+                                         current.map_or("unknown".to_string(), |l| l.to_string()),
+                                         pi.license);
+                    match licenses.get(&pi.license) {
+                        Some(lid) => pic.license_id = lid.clone(),
+                        None => {}
+                    }
+                    if harvested.contains(&pi.license) {
+                        // still a license we harvest, keep the picture with the new one
+                        info!("picture {}: {}", pic.id, reason);
+                        pic.set_status(domain::PICTURE_ACTIVE, reason);
+                    } else {
+                        pic.set_status(domain::PICTURE_RELICENSED, reason);
+                    }
+                }
+            },
+            Err(PhotoInfoError::NotFound) => {
+                pic.set_status(domain::PICTURE_DELETED, "photo not found on flickr");
+            },
+            Err(PhotoInfoError::PermissionDenied) => {
+                pic.set_status(domain::PICTURE_PRIVATE, "photo is not public anymore");
+            },
+            Err(PhotoInfoError::RequestError(e)) => {
+                warn!("unable to verify picture {}, {}", pic.id, e);
+                continue
+            },
+        }
+
+        if &*pic.status == domain::PICTURE_ACTIVE {
+            report.update("pictures", picture_key(&pic), "verified");
+            if !report.dry_run {
+                domain::dao::update_picture_status(conn, &pic).unwrap();
+            }
+            continue
+        }
+
+        pictures_changed += 1;
+        info!("picture {} is {}: {}", pic.id, pic.status, pic.status_reason.clone().unwrap());
+        if purge {
+            report.update("pictures", picture_key(&pic),
+                          format!("purged, status {}: {}", pic.status, pic.status_reason.clone().unwrap()));
+            if report.dry_run {
+                continue
+            }
+            match domain::dao::purge_picture(conn, &pic) {
+                Ok(_) => debug!("picture purged: {:?}", pic),
+                Err(e) => panic!(format!("{}", e)),
+            }
+        } else {
+            report.update("pictures", picture_key(&pic),
+                          format!("status {}: {}", pic.status, pic.status_reason.clone().unwrap()));
+            if !report.dry_run {
+                domain::dao::update_picture_status(conn, &pic).unwrap();
+            }
+        }
+    }
+
+    info!("{} pictures not available anymore", pictures_changed);
+}
+
+/// compute the credit line of every picture from its metadata and license
+pub fn update_attributions(conn: &PgConnection, report: &mut Report) {
+    let licenses = domain::dao::list_licenses(conn)
+        .into_iter()
+        .map(|l| (l.id.clone(), l))
+        .collect::<HashMap<_, _>>();
+    let with_derivatives = domain::dao::list_picture_ids_with_derivatives(conn);
+    let mut attributions_updated = 0;
+
+    for mut pic in domain::dao::list_pictures(conn) {
+        let l = match licenses.get(&pic.license_id) {
+            Some(l) => l,
+            None => continue,
+        };
+        let modified = with_derivatives.contains(&pic.id);
+        let a = Attribution::new(&pic, l, modified);
+        if pic.set_attribution(a) {
+            report.update("pictures", picture_key(&pic), "attribution");
+            if !report.dry_run {
+                domain::dao::update_picture_attribution(conn, &pic);
+            }
+            attributions_updated += 1;
+        }
+    }
+
+    info!("{} pictures attributions updated", attributions_updated);
+}
+
+/// load a picture to hash it, from the mirror if possible or by
+/// downloading its smallest rendition
+fn load_picture_image(conn: &PgConnection,
+                      mirror_dir: Option<&str>,
+                      pic: &domain::Picture) -> Result<image::DynamicImage, String> {
+    use std::fs::File;
+    use std::io::Read;
+
+    match (mirror_dir, domain::dao::list_mirrored_picture_sizes(conn, &*pic.id).into_iter().next()) {
+        (Some(dir), Some(ps)) => {
+            let path = mirror::path_for(Path::new(dir), &*ps.sha256.unwrap(), &*ps.mime_type.unwrap());
+            let mut buf = vec![];
+            return File::open(&path)
+                .and_then(|mut f| f.read_to_end(&mut buf))
+                .map_err(|e| format!("unable to read {}, {}", path.display(), e))
+                .and_then(|_| derivatives::load(&buf))
+        },
+        _ => {}
+    }
+
+    let url = domain::dao::list_picture_sizes(conn, &*pic.id)
+        .into_iter()
+        .min_by_key(|ps| ps.width)
+        .map_or(pic.url.clone(), |ps| ps.url);
+    mirror::download(&*url).and_then(|buf| derivatives::load(&buf))
+}
+
+pub fn dedupe_pictures(conn: &PgConnection,
+                       monuments: &Vec<Monument>,
+                       mirror_dir: Option<&str>,
+                       threshold: u32,
+                       report: &mut Report) {
+    let mut duplicates_found = 0;
+
     for m in monuments {
-        m.site = remove_tags(&re, &m.site);
-        m.long_description = remove_tags(&re, &m.long_description);
-        m.short_description = remove_tags(&re, &m.short_description);
-        domain::dao::update_monument(conn, m);
+        let mut pictures = domain::dao::list_pictures_by_monument_id(conn, &*m.id);
+        // the inactive pictures are not grouped, no need to hash them
+        for pic in pictures.iter_mut().filter(|p| p.phash.is_none() && &*p.status == domain::PICTURE_ACTIVE) {
+            match load_picture_image(conn, mirror_dir, pic) {
+                Ok(img) => {
+                    pic.phash = Some(dedupe::dhash(&img) as i64);
+                    report.update("pictures", picture_key(pic), "phash");
+                    if !report.dry_run {
+                        domain::dao::update_picture_phash(conn, pic);
+                    }
+                },
+                Err(e) => warn!("unable to hash picture {}, {}", pic.id, e),
+            }
+        }
+
+        // the links to a picture which is not active anymore are cleared,
+        // its duplicates are grouped again with the active ones
+        let groups = dedupe::groups(&pictures, threshold);
+        for p in &pictures {
+            let original = dedupe::original(&groups, &*p.id);
+            if p.duplicate_of.as_ref().map(|s| &**s) != original {
+                report.update("pictures", picture_key(p),
+                              format!("duplicate of {}", original.unwrap_or("nothing")));
+                if !report.dry_run {
+                    domain::dao::update_picture_duplicate_of(conn, &*p.id, original);
+                }
+            }
+            if original.is_some() {
+                debug!("picture {} is a duplicate of {}", p.id, original.unwrap());
+                duplicates_found += 1;
+            }
+        }
+    }
+
+    info!("{} duplicate pictures flagged", duplicates_found);
+}
+
+/// convert the texts of monuments imported before the conversion at import
+fn remove_html_tags(conn: &PgConnection, monuments: &mut Vec<Monument>, keep_html: bool, report: &mut Report) {
+    let mut changed = vec![];
+    for m in monuments.iter_mut() {
+        let fields = m.clean_html(keep_html);
+        if fields.is_empty() {
+            continue
+        }
+        report.update("monuments", monument_key(m), fields.join(", "));
+        changed.push(m.clone());
+    }
+
+    // only the monuments whose texts changed
+    if !report.dry_run {
+        match domain::dao::update_monuments(conn, &*changed) {
+            Ok(n) => debug!("{} monuments texts updated", n),
+            Err(e) => panic!(format!("{}", e)),
+        }
     }
+    info!("{} monuments texts cleaned", changed.len());
+}
+
+/// import the monuments of the whc xml and their supplementary files
+fn sync_monuments(conn: &PgConnection, o: &SyncOptions, report: &mut Report) -> Result<(), String> {
+    let whl_payload = unesco_xml::get(o.xml.clone())?;
+    let monuments = read_xml(&*whl_payload, o.keep_html);
+
+    if report.dry_run || o.batch_commit {
+        return import_monuments(conn, monuments, o, report)
+    }
+    // all or nothing: the monuments and their derived tables are
+    // committed together, or the database is left untouched
+    conn.transaction(|| import_monuments(conn, monuments, o, report))
+        .map_err(|e| format!("monument sync rolled back, {}", transaction_error(e)))?;
+    info!("monument sync committed");
+    Ok(())
+}
+
+fn import_monuments(conn: &PgConnection, monuments: Vec<Monument>, o: &SyncOptions, report: &mut Report)
+                    -> Result<(), String> {
+    insert_monuments(conn, monuments, o.batch_commit, report)?;
+
+    match o.components {
+        Some(ref f) => components::import(conn, f, report)?,
+        None => {},
+    }
+    match o.search_overrides {
+        Some(ref f) => overrides::import(conn, f, report)?,
+        None => {},
+    }
+    Ok(())
+}
+
+/// get the pictures from the enabled providers, then check and process them
+fn harvest_pictures(conn: &PgConnection, h: &HarvestOptions, media: &MediaOptions, report: &mut Report)
+                    -> Result<(), String> {
+    let monuments = domain::dao::list_monuments(conn);
+    let mut providers: Vec<Box<PictureProvider>> = vec![];
+    match h.flickr_key {
+        Some(ref key) => {
+            let licenses = insert_licenses(conn, key, report);
+            providers.push(Box::new(FlickrProvider::new(key.clone(), licenses, h.flickr_licenses.clone())));
+        },
+        None => {},
+    }
+    if h.commons {
+        providers.push(Box::new(CommonsProvider::new(h.commons_api.clone())));
+    }
+    for f in &h.provider_fixtures {
+        providers.push(Box::new(FixtureProvider::from_file(f)?));
+    }
+    if !providers.is_empty() {
+        let mut policy = match h.refresh_policy {
+            Some(ref f) => freshness::Policy::from_file(f, h.refresh_days)?,
+            None => freshness::Policy::new(h.refresh_days),
+        };
+        policy.force = h.force.clone();
+        insert_pictures(conn, &monuments, &providers, &policy, report);
+    }
+
+    match h.flickr_key {
+        Some(ref key) => {
+            if h.backfill_pictures {
+                backfill_pictures(conn, key, report);
+            }
+
+            if h.verify_pictures {
+                let licenses = domain::dao::list_licenses(conn)
+                    .into_iter()
+                    .filter_map(|l| l.flickr_id.map(|fid| (fid, l.id)))
+                    .collect();
+                verify_pictures(conn, key, &licenses, &*h.flickr_licenses,
+                                &*h.verify_action == "purge", report);
+            }
+        },
+        None => {},
+    }
+
+    // download pictures locally if asked
+    match media.mirror_dir {
+        Some(_) if report.dry_run => {
+            info!("pictures are not mirrored in a dry run");
+            if media.derivatives {
+                report_derivatives(conn, &derivatives_profile(media)?, &media.mirror_sizes, report);
+            }
+        },
+        Some(ref dir) => {
+            mirror_pictures(conn, dir, &media.mirror_sizes, media.mirror_workers);
+
+            if media.derivatives {
+                generate_derivatives(conn, dir, &derivatives_profile(media)?);
+            }
+        },
+        None => {},
+    }
+
+    if media.dedupe {
+        dedupe_pictures(conn, &monuments, media.mirror_dir.as_ref().map(|s| &**s),
+                        media.dedupe_threshold, report);
+    }
+
+    update_attributions(conn, report);
+    Ok(())
+}
+
+fn derivatives_profile(media: &MediaOptions) -> Result<derivatives::Profile, String> {
+    match media.derivatives_profile {
+        Some(ref f) => derivatives::Profile::from_file(f),
+        None => Ok(derivatives::Profile::default()),
+    }
+}
+
+fn clean_html(conn: &PgConnection, keep_html: bool, report: &mut Report) {
+    let mut monuments = domain::dao::list_monuments(conn);
+    remove_html_tags(conn, &mut monuments, keep_html, report);
+}
+
+fn run(conn: &PgConnection, cmd: Command, report: &mut Report) -> Result<(), String> {
+    // refuse to work on a schema which is not the one of the binary,
+    // run-all migrates it first unless in a dry run
+    match cmd {
+        Command::Migrate(_) | Command::Repair(_) | Command::ConfigCheck(_) => {},
+        Command::RunAll { .. } if !report.dry_run => migrate::up(conn, None)?,
+        _ => migrate::check(conn)?,
+    }
+
+    match cmd {
+        Command::Migrate(cmd) => migrate::run(conn, cmd)?,
+        Command::SyncMonuments(o) => sync_monuments(conn, &o, report)?,
+        Command::SyncLicenses { flickr_key } => { insert_licenses(conn, &*flickr_key, report); },
+        Command::HarvestPictures(h, media) => harvest_pictures(conn, &h, &media, report)?,
+        Command::CleanHtml { keep_html } => clean_html(conn, keep_html, report),
+        Command::Export { output } => export::run(conn, output)?,
+        Command::Stats => stats::run(conn)?,
+        Command::RunAll { sync, harvest, media } => {
+            sync_monuments(conn, &sync, report)?;
+            harvest_pictures(conn, &harvest, &media, report)?;
+        },
+        Command::Curation(cmd) => curation::run(conn, cmd)?,
+        Command::Repair(cmd) => repair::run(conn, cmd, report)?,
+        Command::ConfigCheck(_) => {/* done without database */},
+    }
+    Ok(())
 }
 
 fn main() {
     let _ = env_logger::init();
-    let args = parse_cmdline();
-    let whl_payload = match unesco_xml::get(args.xml) {
-        Ok(p) => p,
+    let args = match cli::parse_cmdline() {
+        Ok(args) => args,
         Err(e) => {
             error!("{}", e);
             return
         }
     };
-    // get pq connection
-    let conn = establish_connection(&*args.pq_addr);
-
-    // run migration if needed
-    run_migrations(&conn, args.migrations);
-
-    // first insert monuments
-    let mut monuments = read_xml(&*whl_payload);
-    insert_monuments(&conn, &mut monuments);
-
-    let mut monuments = domain::dao::list_monuments(&conn);
-    // then if api key for flickr is used, get picture from flickr
-    match args.flickr_key {
-        Some(key) => {
-            insert_licenses(&conn, &key);
+    match (args.http_record.clone(), args.http_replay.clone()) {
+        (Some(dir), _) => http::set_mode(http::Mode::Record(dir.into())),
+        (_, Some(dir)) => http::set_mode(http::Mode::Replay(dir.into())),
+        _ => {},
+    }
 
-            let licenses = domain::dao::list_licenses(&conn);
-            let mut lmap = HashMap::new();
-            for l in licenses {
-                lmap.insert(l.flickr_id, l.id);
+    let pq_addr = match args.command {
+        Command::ConfigCheck(ref c) => {
+            match c.masked().to_toml() {
+                Ok(t) => {
+                    println!("config ok");
+                    print!("{}", t);
+                },
+                Err(e) => error!("{}", e),
             }
-            insert_pictures(&conn, &monuments, &key, lmap);
+            return
         },
-        None => {},
-    }
+        // always set for the commands using the database
+        _ => args.pq_addr.clone().unwrap(),
+    };
 
-    // remove html
-    remove_html_tags(&conn, &mut monuments);
+    // get pq connection
+    let conn = establish_connection(&*pq_addr);
+    let mut report = Report::new(args.dry_run);
+    match run(&conn, args.command, &mut report) {
+        Ok(_) => {},
+        Err(e) => error!("{}", e),
+    }
+    if report.dry_run {
+        report.print();
+    }
 }
@@ -0,0 +1,352 @@
+// Copyright 2017 Jeremy Letang.
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+
+//! The migrations of the schema, embedded in the binary by the build script
+//! from the `migrations` directory.
+//!
+//! The applied versions are recorded in the `__diesel_schema_migrations`
+//! table of diesel, so a database migrated from a directory by an older
+//! whlp or by diesel_cli is seen as such.
+
+use clap::{self, App, Arg, ArgMatches, SubCommand};
+use diesel::Connection;
+use diesel::pg::PgConnection;
+use diesel::result::Error;
+use transaction_error;
+
+pub struct Migration {
+    /// the name of its directory, the version then a description
+    pub name: &'static str,
+    pub up: &'static str,
+    pub down: &'static str,
+}
+
+impl Migration {
+    pub fn version(&self) -> &'static str {
+        self.name.split('_').next().unwrap()
+    }
+}
+
+include!(concat!(env!("OUT_DIR"), "/migrations.rs"));
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum Command {
+    Status,
+    /// the tables of the database, in the format of `domain/schema.rs`
+    PrintSchema,
+    /// compare `domain/schema.rs` with the database
+    CheckSchema,
+    /// apply the pending migrations, up to a version
+    Up { to: Option<String> },
+    /// revert the last migration, or the migrations after a version
+    Down { to: Option<String> },
+}
+
+pub fn subcommand<'a, 'b>() -> App<'a, 'b> {
+    let to = Arg::with_name("to")
+        .long("to")
+        .help("version of the migration, the timestamp starting its name")
+        .takes_value(true);
+
+    SubCommand::with_name("migrate")
+        .about("list, apply or revert the database migrations embedded in whlp")
+        .setting(clap::AppSettings::SubcommandRequiredElseHelp)
+        .subcommand(SubCommand::with_name("status")
+                    .about("list the applied and pending migrations"))
+        .subcommand(SubCommand::with_name("up")
+                    .about("apply the pending migrations, all of them or up to --to included")
+                    .arg(to.clone()))
+        .subcommand(SubCommand::with_name("down")
+                    .about("revert the last migration, or every migration after --to")
+                    .arg(to))
+        .subcommand(SubCommand::with_name("print-schema")
+                    .about("print the tables of the database as written in src/domain/schema.rs"))
+        .subcommand(SubCommand::with_name("check-schema")
+                    .about("check that the schema built in whlp is the one of the database"))
+}
+
+pub fn from_matches(matches: &ArgMatches) -> Result<Command, String> {
+    fn to(m: &ArgMatches) -> Result<Option<String>, String> {
+        match m.value_of("to") {
+            Some(v) if MIGRATIONS.iter().any(|m| m.version() == v) => Ok(Some(v.into())),
+            Some(v) => Err(format!("unknown migration version {}, see migrate status", v)),
+            None => Ok(None),
+        }
+    }
+
+    match matches.subcommand() {
+        ("status", Some(_)) => Ok(Command::Status),
+        ("up", Some(m)) => Ok(Command::Up { to: to(m)? }),
+        ("down", Some(m)) => Ok(Command::Down { to: to(m)? }),
+        ("print-schema", Some(_)) => Ok(Command::PrintSchema),
+        ("check-schema", Some(_)) => Ok(Command::CheckSchema),
+        _ => Err(format!("missing migrate command, see --help")),
+    }
+}
+
+pub fn run(conn: &PgConnection, cmd: Command) -> Result<(), String> {
+    match cmd {
+        Command::Status => status(conn),
+        Command::Up { to } => up(conn, to.as_ref().map(|s| &**s)),
+        Command::Down { to } => down(conn, to.as_ref().map(|s| &**s)),
+        Command::PrintSchema => schema(conn).map(|s| print!("{}", s)),
+        Command::CheckSchema => check_schema(conn),
+    }
+}
+
+/// the versions recorded in the database
+fn applied(conn: &PgConnection) -> Result<Vec<String>, String> {
+    use diesel::LoadDsl;
+    use diesel::expression::dsl::sql;
+    use diesel::types::{Bool, VarChar};
+
+    let exists = sql::<Bool>("SELECT EXISTS (SELECT 1 FROM information_schema.tables \
+                              WHERE table_name = '__diesel_schema_migrations')")
+        .get_result::<bool>(conn)
+        .map_err(|e| format!("unable to read the migrations, {}", e))?;
+    if !exists {
+        return Ok(vec![])
+    }
+    sql::<VarChar>("SELECT version FROM __diesel_schema_migrations ORDER BY version")
+        .load::<String>(conn)
+        .map_err(|e| format!("unable to read the migrations, {}", e))
+}
+
+/// the applied versions this binary does not know, from a newer whlp
+fn unknown(applied: &[String]) -> Vec<String> {
+    applied.iter()
+        .filter(|v| !MIGRATIONS.iter().any(|m| m.version() == &***v))
+        .cloned()
+        .collect()
+}
+
+fn pending(applied: &[String]) -> Vec<&'static Migration> {
+    MIGRATIONS.iter()
+        .filter(|m| !applied.iter().any(|v| v == m.version()))
+        .collect()
+}
+
+/// refuse to run against a schema another version of whlp migrated
+fn check_ahead(applied: &[String]) -> Result<(), String> {
+    let unknown = unknown(applied);
+    if unknown.is_empty() {
+        Ok(())
+    } else {
+        Err(format!("the database schema is ahead of this binary, unknown migrations {}, update whlp",
+                    unknown.join(", ")))
+    }
+}
+
+/// check that the schema is the one of the binary, before any other command
+pub fn check(conn: &PgConnection) -> Result<(), String> {
+    let applied = applied(conn)?;
+    check_ahead(&*applied)?;
+    let pending = pending(&*applied);
+    if pending.is_empty() {
+        Ok(())
+    } else {
+        Err(format!("the database schema is behind this binary, {} pending migrations, run migrate up",
+                    pending.len()))
+    }
+}
+
+fn status(conn: &PgConnection) -> Result<(), String> {
+    let applied = applied(conn)?;
+    for m in MIGRATIONS {
+        let state = if applied.iter().any(|v| v == m.version()) { "applied" } else { "pending" };
+        println!("{}\t{}", state, m.name);
+    }
+    for v in unknown(&*applied) {
+        println!("unknown\t{}", v);
+    }
+    Ok(())
+}
+
+fn setup(conn: &PgConnection) -> Result<(), String> {
+    conn.execute("CREATE TABLE IF NOT EXISTS __diesel_schema_migrations (\
+                  version VARCHAR(50) PRIMARY KEY NOT NULL, \
+                  run_on TIMESTAMP NOT NULL DEFAULT CURRENT_TIMESTAMP)")
+        .map(|_| ())
+        .map_err(|e| format!("unable to create the migrations table, {}", e))
+}
+
+/// run the sql of a migration and record it, in a transaction
+fn migrate(conn: &PgConnection, m: &Migration, sql: &str, record: String) -> Result<(), String> {
+    use diesel::connection::SimpleConnection;
+
+    conn.transaction::<_, Error, _>(|| {
+        conn.batch_execute(sql)?;
+        conn.execute(&*record)?;
+        Ok(())
+    }).map_err(|e| format!("migration {} rolled back, {}", m.name, transaction_error(e)))
+}
+
+/// apply the pending migrations up to `to` included, or all of them
+pub fn up(conn: &PgConnection, to: Option<&str>) -> Result<(), String> {
+    setup(conn)?;
+    let applied = applied(conn)?;
+    check_ahead(&*applied)?;
+
+    let pending = pending(&*applied)
+        .into_iter()
+        .filter(|m| to.map_or(true, |t| m.version() <= t))
+        .collect::<Vec<_>>();
+    for m in &pending {
+        info!("applying migration {}", m.name);
+        migrate(conn, m, m.up, format!("INSERT INTO __diesel_schema_migrations (version) VALUES ('{}')",
+                                       m.version()))?;
+    }
+    info!("{} migrations applied", pending.len());
+    Ok(())
+}
+
+/// revert the applied migrations after `to`, or the last one
+pub fn down(conn: &PgConnection, to: Option<&str>) -> Result<(), String> {
+    setup(conn)?;
+    let applied = applied(conn)?;
+    check_ahead(&*applied)?;
+
+    let mut reverted = MIGRATIONS.iter()
+        .rev()
+        .filter(|m| applied.iter().any(|v| v == m.version()));
+    let reverted = match to {
+        Some(t) => reverted.take_while(|m| m.version() > t).collect::<Vec<_>>(),
+        None => reverted.next().into_iter().collect(),
+    };
+    for m in &reverted {
+        info!("reverting migration {}", m.name);
+        migrate(conn, m, m.down, format!("DELETE FROM __diesel_schema_migrations WHERE version = '{}'",
+                                         m.version()))?;
+    }
+    info!("{} migrations reverted", reverted.len());
+    Ok(())
+}
+
+/// the diesel type of a postgres type, named as in `pg_type`
+fn diesel_type(udt: &str) -> Result<String, String> {
+    if udt.starts_with('_') {
+        return diesel_type(&udt[1..]).map(|t| format!("Array<{}>", t))
+    }
+    let t = match udt {
+        "varchar" => "VarChar",
+        "text" => "Text",
+        "int2" => "Int2",
+        "int4" => "Int4",
+        "int8" => "Int8",
+        "float4" => "Float4",
+        "float8" => "Float8",
+        "bool" => "Bool",
+        "timestamp" => "Timestamp",
+        "date" => "Date",
+        "bytea" => "Binary",
+        _ => return Err(format!("no diesel type for the postgres type {}", udt)),
+    };
+    Ok(t.to_string())
+}
+
+/// the `table!` of each table of the database, by table name
+fn schema(conn: &PgConnection) -> Result<String, String> {
+    use diesel::LoadDsl;
+    use diesel::expression::dsl::sql;
+    use diesel::types::{Bool, VarChar};
+
+    let columns = sql::<(VarChar, VarChar, VarChar, Bool)>(
+        "SELECT table_name::text, column_name::text, udt_name::text, is_nullable = 'YES' \
+         FROM information_schema.columns \
+         WHERE table_schema = 'public' AND table_name <> '__diesel_schema_migrations' \
+         ORDER BY table_name, ordinal_position")
+        .load::<(String, String, String, bool)>(conn)
+        .map_err(|e| format!("unable to read the schema, {}", e))?;
+    let keys = sql::<(VarChar, VarChar)>(
+        "SELECT tc.table_name::text, kcu.column_name::text \
+         FROM information_schema.table_constraints tc \
+         JOIN information_schema.key_column_usage kcu \
+           ON kcu.constraint_name = tc.constraint_name AND kcu.table_name = tc.table_name \
+         WHERE tc.constraint_type = 'PRIMARY KEY' AND tc.table_schema = 'public' \
+         ORDER BY kcu.ordinal_position")
+        .load::<(String, String)>(conn)
+        .map_err(|e| format!("unable to read the primary keys, {}", e))?;
+
+    let mut tables = vec![];
+    let mut current: Option<String> = None;
+    let mut block = String::new();
+    for (table, column, udt, nullable) in columns {
+        if current.as_ref() != Some(&table) {
+            if current.is_some() {
+                block.push_str("    }\n}\n");
+                tables.push(block);
+            }
+            let key = keys.iter()
+                .filter(|&&(ref t, _)| *t == table)
+                .map(|&(_, ref c)| c.clone())
+                .collect::<Vec<_>>();
+            block = format!("table! {{\n    {} ({}) {{\n", table, key.join(", "));
+            current = Some(table);
+        }
+        let t = diesel_type(&*udt)?;
+        let t = if nullable { format!("Nullable<{}>", t) } else { t };
+        block.push_str(&*format!("        {} -> {},\n", column, t));
+    }
+    if current.is_some() {
+        block.push_str("    }\n}\n");
+        tables.push(block);
+    }
+    Ok(tables.join("\n"))
+}
+
+/// the tables of `domain/schema.rs`, after its header
+fn built_schema() -> &'static str {
+    let schema = include_str!("domain/schema.rs");
+    &schema[schema.find("table! {").unwrap_or(0)..]
+}
+
+/// fail on the tables of the database not written as in `domain/schema.rs`
+fn check_schema(conn: &PgConnection) -> Result<(), String> {
+    let current = schema(conn)?;
+    // a block by table, starting with its name
+    let tables = |s: &str| {
+        s.split("table! {")
+            .map(|t| t.trim().to_string())
+            .filter(|t| !t.is_empty())
+            .collect::<Vec<_>>()
+    };
+    let built = tables(built_schema());
+    let current = tables(&*current);
+
+    let name = |t: &String| t.split_whitespace().next().unwrap_or("").to_string();
+    let mut differences = built.iter()
+        .filter(|t| !current.contains(t))
+        .chain(current.iter().filter(|t| !built.contains(t)))
+        .map(|t| name(t))
+        .collect::<Vec<_>>();
+    differences.sort();
+    differences.dedup();
+    if differences.is_empty() {
+        info!("the schema of whlp is the one of the database");
+        Ok(())
+    } else {
+        Err(format!("src/domain/schema.rs differs from the database on the tables {}, \
+                     replace it with the output of migrate print-schema", differences.join(", ")))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use test_db;
+
+    #[test]
+    fn migrated_schema_is_the_built_one() {
+        test_db::with_connection(|conn| {
+            // from an empty database, whatever the test database had
+            down(conn, Some("0")).unwrap();
+            assert!(applied(conn).unwrap().is_empty());
+            up(conn, None).unwrap();
+            check_schema(conn).unwrap();
+        });
+    }
+}
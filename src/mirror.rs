@@ -0,0 +1,108 @@
+// Copyright 2017 Jeremy Letang.
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+
+//! Download pictures into a content-addressed directory.
+//!
+//! A file is stored at `<root>/<h[0..2]>/<h[2..4]>/<h>.<ext>` where `h` is
+//! the hex sha256 of its content, so the same image downloaded twice is
+//! only written once.
+
+use http;
+use sha2::{Digest, Sha256};
+use std::fs::{self, File};
+use std::io::Write;
+use std::path::{Path, PathBuf};
+
+#[derive(Clone, Debug)]
+pub struct MirroredFile {
+    pub sha256: String,
+    pub size: i64,
+    pub mime_type: String,
+    pub path: PathBuf,
+}
+
+/// path of a file in the mirror from its hash and mime type
+pub fn path_for(root: &Path, sha256: &str, mime_type: &str) -> PathBuf {
+    root.join(&sha256[0..2])
+        .join(&sha256[2..4])
+        .join(format!("{}.{}", sha256, extension(mime_type)))
+}
+
+/// download `url` and store it in the mirror at `root`, the file is not
+/// written again if it is already present
+pub fn mirror(root: &Path, url: &str) -> Result<MirroredFile, String> {
+    let buf = download(url)?;
+    store(root, &buf)
+}
+
+pub fn store(root: &Path, buf: &[u8]) -> Result<MirroredFile, String> {
+    let sha256 = hex_sha256(buf);
+    let mime_type = sniff_mime_type(buf).to_string();
+    let path = path_for(root, &*sha256, &*mime_type);
+
+    if path.exists() {
+        debug!("file already mirrored: {}", path.display());
+    } else {
+        write_atomic(&path, buf)?;
+        debug!("file mirrored: {}", path.display());
+    }
+
+    Ok(MirroredFile {
+        sha256: sha256,
+        size: buf.len() as i64,
+        mime_type: mime_type,
+        path: path,
+    })
+}
+
+pub fn download(url: &str) -> Result<Vec<u8>, String> {
+    info!("downloading {}", url);
+    http::get_bytes(url, url)
+}
+
+fn write_atomic(path: &Path, buf: &[u8]) -> Result<(), String> {
+    let dir = path.parent().unwrap();
+    fs::create_dir_all(dir)
+        .map_err(|e| format!("unable to create directory {}, {}", dir.display(), e))?;
+    // write in a temporary file first so an interrupted download never
+    // leaves a truncated file at its final path
+    let tmp = path.with_extension("part");
+    File::create(&tmp)
+        .and_then(|mut f| f.write_all(buf))
+        .and_then(|_| fs::rename(&tmp, path))
+        .map_err(|e| format!("unable to write file {}, {}", path.display(), e))
+}
+
+fn hex_sha256(buf: &[u8]) -> String {
+    let mut hasher = Sha256::default();
+    hasher.input(buf);
+    hasher.result().iter().map(|b| format!("{:02x}", b)).collect()
+}
+
+fn sniff_mime_type(buf: &[u8]) -> &'static str {
+    if buf.starts_with(&[0xff, 0xd8, 0xff]) {
+        "image/jpeg"
+    } else if buf.starts_with(b"\x89PNG\r\n\x1a\n") {
+        "image/png"
+    } else if buf.starts_with(b"GIF87a") || buf.starts_with(b"GIF89a") {
+        "image/gif"
+    } else if buf.len() >= 12 && &buf[0..4] == b"RIFF" && &buf[8..12] == b"WEBP" {
+        "image/webp"
+    } else {
+        "application/octet-stream"
+    }
+}
+
+fn extension(mime_type: &str) -> &'static str {
+    match mime_type {
+        "image/jpeg" => "jpg",
+        "image/png" => "png",
+        "image/gif" => "gif",
+        "image/webp" => "webp",
+        _ => "bin",
+    }
+}
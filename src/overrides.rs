@@ -0,0 +1,84 @@
+// Copyright 2017 Jeremy Letang.
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+
+//! Custom searches for the monuments whose name does not find their
+//! pictures, read from a json file keyed by the `id_number` of the sites.
+//!
+//! ```json
+//! {
+//!   "668": { "query": "Angkor Wat", "tags": ["angkor"] },
+//!   "1001": { "bbox": "2.29,48.85,2.30,48.86", "group_id": "12345@N00" }
+//! }
+//! ```
+
+use diesel::pg::PgConnection;
+use domain::{self, SearchOverride};
+use report::Report;
+use serde_json;
+use std::collections::HashMap;
+use std::fs::File;
+use std::io::Read;
+
+#[derive(Clone, Debug, Default, Serialize, Deserialize)]
+pub struct Entry {
+    pub query: Option<String>,
+    #[serde(default)]
+    pub tags: Vec<String>,
+    /// min_lon,min_lat,max_lon,max_lat
+    pub bbox: Option<String>,
+    pub group_id: Option<String>,
+}
+
+pub fn from_file(file: &str) -> Result<HashMap<String, Entry>, String> {
+    info!("reading search overrides: '{}'", file);
+    let mut buf = String::new();
+    match File::open(file).and_then(|mut f| f.read_to_string(&mut buf)) {
+        Ok(_) => serde_json::from_str::<HashMap<String, Entry>>(&*buf)
+            .map_err(|e| format!("invalid search overrides, {}", e)),
+        Err(e) => Err(format!("unable to read search overrides, {}", e)),
+    }
+}
+
+fn describe(o: &SearchOverride) -> String {
+    format!("query: {:?}, tags: {:?}, bbox: {:?}, group: {:?}", o.query, o.tags, o.bbox, o.group_id)
+}
+
+/// save the overrides of the file, replacing the existing ones of the same sites
+pub fn import(conn: &PgConnection, file: &str, report: &mut Report) -> Result<(), String> {
+    let entries = from_file(file)?;
+    let existing = domain::dao::list_search_overrides(conn)
+        .into_iter()
+        .map(|o| (o.id_number, o))
+        .collect::<HashMap<_, _>>();
+
+    for (n, e) in entries {
+        let n = n.parse::<i32>()
+            .map_err(|_| format!("invalid site '{}' in search overrides, must be a number", n))?;
+        let previous = existing.get(&n).cloned();
+        let mut o = previous.clone().unwrap_or(SearchOverride::new(n));
+        o.query = e.query;
+        o.tags = e.tags;
+        o.bbox = e.bbox;
+        o.group_id = e.group_id;
+        if o.bbox.is_some() && o.bbox().is_none() {
+            return Err(format!("invalid bbox for site {}, expected min_lon,min_lat,max_lon,max_lat", n));
+        }
+        match previous {
+            Some(ref p) if p.query == o.query && p.tags == o.tags &&
+                p.bbox == o.bbox && p.group_id == o.group_id => continue,
+            Some(_) => report.update("search_overrides", n.to_string(), describe(&o)),
+            None => report.insert("search_overrides", n.to_string(), describe(&o)),
+        }
+        if report.dry_run {
+            continue
+        }
+        o.touch();
+        domain::dao::save_search_override(conn, &o).map_err(|e| format!("{}", e))?;
+        debug!("search override saved: {:?}", o);
+    }
+    Ok(())
+}
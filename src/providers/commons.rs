@@ -0,0 +1,140 @@
+// Copyright 2017 Jeremy Letang.
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+
+use chrono::NaiveDateTime;
+use commons_api;
+use domain::{Monument, Picture, SearchOverride};
+use regex::Regex;
+use std::str::FromStr;
+use super::{Fetched, PictureProvider, ProviderLicense, Size};
+
+pub const COMMONS: &'static str = "commons";
+
+/// radius of the geosearch around the monument, in meters
+const GEOSEARCH_RADIUS: u32 = 1000;
+const THUMB_WIDTH: u32 = 320;
+
+pub struct CommonsProvider {
+    api: String,
+    tags: Regex,
+}
+
+impl CommonsProvider {
+    /// `api` is the url of the mediawiki api, a mock server can be used
+    /// instead of commons
+    pub fn new<S: Into<String>>(api: S) -> CommonsProvider {
+        CommonsProvider {
+            api: api.into(),
+            tags: Regex::new("<[^>]*>").unwrap(),
+        }
+    }
+
+    fn strip_tags(&self, s: Option<String>) -> Option<String> {
+        s.map(|s| self.tags.replace_all(&*s, "").trim().to_string())
+            .and_then(|s| if s.is_empty() { None } else { Some(s) })
+    }
+}
+
+impl PictureProvider for CommonsProvider {
+    fn name(&self) -> &str {
+        COMMONS
+    }
+
+    fn search(&self, m: &Monument, o: Option<&SearchOverride>) -> Result<Vec<String>, String> {
+        let mut ids = vec![];
+
+        // the query of an override is the name of the commons category
+        let category = o.and_then(|o| o.query.clone()).or(m.site.clone());
+        match category {
+            Some(ref name) => {
+                for p in commons_api::category_members(&*self.api, name)? {
+                    ids.push(p.pageid.to_string());
+                }
+            },
+            None => {}
+        }
+
+        match (o.and_then(|o| o.bbox()), m.latitude, m.longitude) {
+            (Some((min_lon, min_lat, max_lon, max_lat)), _, _) => {
+                for p in commons_api::geosearch_bbox(&*self.api, min_lon, min_lat, max_lon, max_lat)? {
+                    ids.push(p.pageid.to_string());
+                }
+            },
+            (None, Some(lat), Some(lng)) => {
+                for p in commons_api::geosearch(&*self.api, lat, lng, GEOSEARCH_RADIUS)? {
+                    ids.push(p.pageid.to_string());
+                }
+            },
+            _ => {}
+        }
+
+        ids.sort();
+        ids.dedup();
+        Ok(ids)
+    }
+
+    fn fetch(&self, id: &str) -> Result<Fetched, String> {
+        let page = commons_api::image_info(&*self.api, id, THUMB_WIDTH)?;
+        let ii = &page.imageinfo[0];
+
+        let license = match ii.meta("LicenseShortName") {
+            Some(name) => ProviderLicense {
+                flickr_id: None,
+                name: name,
+                url: ii.meta("LicenseUrl"),
+            },
+            None => return Err(format!("commons file {} has no license", id)),
+        };
+
+        let author = self.strip_tags(ii.meta("Artist")).unwrap_or(ii.user.clone());
+        let mut pic = Picture::new(COMMONS, id, String::new(), String::new(),
+                                   author, ii.url.clone());
+
+        let title = page.title.trim_left_matches("File:");
+        let title = match title.rfind('.') {
+            Some(i) => &title[..i],
+            None => title,
+        };
+        pic.title = ii.meta("ObjectName").or(Some(title.to_string()));
+        pic.description = self.strip_tags(ii.meta("ImageDescription"));
+        pic.date_taken = ii.meta("DateTimeOriginal").and_then(|d| {
+            // the value is free text, only keep the date if it starts with one
+            let d = d.chars().take(19).collect::<String>();
+            NaiveDateTime::parse_from_str(&*d, "%Y-%m-%d %H:%M:%S").ok()
+        });
+        pic.owner_nsid = Some(ii.user.clone());
+        pic.page_url = Some(ii.descriptionurl.clone());
+        pic.latitude = ii.meta("GPSLatitude").and_then(|v| FromStr::from_str(&*v).ok());
+        pic.longitude = ii.meta("GPSLongitude").and_then(|v| FromStr::from_str(&*v).ok());
+        pic.tags = ii.meta("Categories")
+            .map_or(vec![], |c| c.split('|').map(|s| s.to_string()).collect());
+        pic.original_width = Some(ii.width);
+        pic.original_height = Some(ii.height);
+
+        let mut sizes = vec![Size {
+            label: "Original".into(),
+            width: ii.width,
+            height: ii.height,
+            url: ii.url.clone(),
+        }];
+        match (ii.thumburl.clone(), ii.thumbwidth, ii.thumbheight) {
+            (Some(url), Some(w), Some(h)) => sizes.push(Size {
+                label: "Thumbnail".into(),
+                width: w,
+                height: h,
+                url: url,
+            }),
+            _ => {}
+        }
+
+        Ok(Fetched {
+            picture: pic,
+            license: license,
+            sizes: sizes,
+        })
+    }
+}
@@ -0,0 +1,317 @@
+// Copyright 2017 Jeremy Letang.
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+
+//! A provider serving pictures from memory, filled by the tests or from a
+//! json fixtures file, to run the harvest without network or api key.
+//!
+//! ```json
+//! {
+//!   "name": "flickr",
+//!   "monuments": { "668": ["123"] },
+//!   "pictures": {
+//!     "123": {
+//!       "author": "someone",
+//!       "url": "https://example.com/123_o.jpg",
+//!       "title": "Some title",
+//!       "license": { "flickr_id": 4, "name": "Attribution License" },
+//!       "sizes": [{ "label": "Original", "width": 800, "height": 600,
+//!                   "url": "https://example.com/123_o.jpg" }]
+//!     }
+//!   }
+//! }
+//! ```
+
+use domain::{Monument, Picture, SearchOverride};
+use serde_json;
+use std::collections::HashMap;
+use std::fs::File;
+use std::io::Read;
+use super::{Fetched, PictureProvider, ProviderLicense, Size};
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct FixtureLicense {
+    pub flickr_id: Option<i32>,
+    pub name: String,
+    pub url: Option<String>,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct FixtureSize {
+    pub label: String,
+    pub width: i32,
+    pub height: i32,
+    pub url: String,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct FixturePicture {
+    pub author: String,
+    pub url: String,
+    pub title: Option<String>,
+    pub description: Option<String>,
+    pub owner_nsid: Option<String>,
+    pub page_url: Option<String>,
+    #[serde(default)]
+    pub tags: Vec<String>,
+    pub license: FixtureLicense,
+    #[serde(default)]
+    pub sizes: Vec<FixtureSize>,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct Fixtures {
+    pub name: String,
+    /// the ids of the pictures returned for each monument `id_number`
+    #[serde(default)]
+    pub monuments: HashMap<String, Vec<String>>,
+    #[serde(default)]
+    pub pictures: HashMap<String, FixturePicture>,
+}
+
+pub struct FixtureProvider {
+    fixtures: Fixtures,
+}
+
+impl FixtureProvider {
+    #[cfg(test)]
+    pub fn new<S: Into<String>>(name: S) -> FixtureProvider {
+        FixtureProvider {
+            fixtures: Fixtures {
+                name: name.into(),
+                monuments: HashMap::new(),
+                pictures: HashMap::new(),
+            }
+        }
+    }
+
+    pub fn from_file(file: &str) -> Result<FixtureProvider, String> {
+        info!("reading provider fixtures: '{}'", file);
+        let mut buf = String::new();
+        match File::open(file).and_then(|mut f| f.read_to_string(&mut buf)) {
+            Ok(_) => serde_json::from_str::<Fixtures>(&*buf)
+                .map(|f| FixtureProvider { fixtures: f })
+                .map_err(|e| format!("invalid provider fixtures, {}", e)),
+            Err(e) => Err(format!("unable to read provider fixtures, {}", e)),
+        }
+    }
+
+    /// return the picture `id` when searching the monument `id_number`
+    #[cfg(test)]
+    pub fn add<S: Into<String>>(&mut self, id_number: i32, id: S, p: FixturePicture) {
+        let id = id.into();
+        self.fixtures.monuments.entry(id_number.to_string())
+            .or_insert(vec![])
+            .push(id.clone());
+        self.fixtures.pictures.insert(id, p);
+    }
+}
+
+impl PictureProvider for FixtureProvider {
+    fn name(&self) -> &str {
+        &*self.fixtures.name
+    }
+
+    fn search(&self, m: &Monument, _: Option<&SearchOverride>) -> Result<Vec<String>, String> {
+        Ok(m.id_number
+            .and_then(|n| self.fixtures.monuments.get(&n.to_string()))
+            .map_or(vec![], |ids| ids.clone()))
+    }
+
+    fn fetch(&self, id: &str) -> Result<Fetched, String> {
+        let fp = match self.fixtures.pictures.get(id) {
+            Some(fp) => fp.clone(),
+            None => return Err(format!("no fixture for picture {}", id)),
+        };
+
+        let mut pic = Picture::new(self.name(), id, String::new(), String::new(),
+                                   fp.author, fp.url);
+        pic.title = fp.title;
+        pic.description = fp.description;
+        pic.owner_nsid = fp.owner_nsid;
+        pic.page_url = fp.page_url;
+        pic.tags = fp.tags;
+        match fp.sizes.iter().find(|s| &*s.label == "Original") {
+            Some(s) => {
+                pic.original_width = Some(s.width);
+                pic.original_height = Some(s.height);
+            },
+            None => {}
+        }
+
+        Ok(Fetched {
+            picture: pic,
+            license: ProviderLicense {
+                flickr_id: fp.license.flickr_id,
+                name: fp.license.name,
+                url: fp.license.url,
+            },
+            sizes: fp.sizes.into_iter().map(|s| Size {
+                label: s.label,
+                width: s.width,
+                height: s.height,
+                url: s.url,
+            }).collect(),
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use diesel::pg::PgConnection;
+    use domain::{self, Monument, TASK_PICTURES};
+    use freshness::Policy;
+    use providers::PictureProvider;
+    use report::Report;
+    use super::*;
+    use test_db;
+
+    const PROVIDER: &'static str = "fixtures";
+
+    fn cc_by() -> FixtureLicense {
+        FixtureLicense {
+            flickr_id: Some(4),
+            name: "Attribution License".into(),
+            url: Some("https://creativecommons.org/licenses/by/2.0/".into()),
+        }
+    }
+
+    fn cc_by_sa() -> FixtureLicense {
+        FixtureLicense {
+            flickr_id: None,
+            name: "CC BY-SA 4.0".into(),
+            url: Some("https://creativecommons.org/licenses/by-sa/4.0/".into()),
+        }
+    }
+
+    fn picture(title: &str, license: FixtureLicense) -> FixturePicture {
+        FixturePicture {
+            author: "someone".into(),
+            url: format!("https://example.com/{}.jpg", title),
+            title: Some(title.into()),
+            description: None,
+            owner_nsid: None,
+            page_url: None,
+            tags: vec![],
+            license: license,
+            sizes: vec![FixtureSize {
+                label: "Original".into(),
+                width: 800,
+                height: 600,
+                url: format!("https://example.com/{}.jpg", title),
+            }],
+        }
+    }
+
+    /// save a monument and read it back
+    fn monument(conn: &PgConnection, id_number: i32) -> Monument {
+        let mut m = Monument::new();
+        m.id_number = Some(id_number);
+        m.site = Some(format!("site {}", id_number));
+        ::insert_monuments(conn, vec![m], false, &mut Report::new(false)).unwrap();
+        domain::dao::find_monument_by_id_number(conn, id_number).unwrap()
+    }
+
+    /// harvest the pictures of the monuments, whatever their freshness
+    fn harvest(conn: &PgConnection, monuments: &Vec<Monument>, provider: FixtureProvider) {
+        let providers: Vec<Box<PictureProvider>> = vec![Box::new(provider)];
+        let mut policy = Policy::new(7);
+        policy.force = monuments.iter().filter_map(|m| m.id_number).collect();
+        ::insert_pictures(conn, monuments, &*providers, &policy, &mut Report::new(false));
+    }
+
+    fn title(p: &Picture) -> String {
+        p.title.clone().unwrap_or_default()
+    }
+
+    #[test]
+    fn license_mapping() {
+        test_db::with_connection(|conn| {
+            let m = monument(conn, 990001);
+            let mut provider = FixtureProvider::new(PROVIDER);
+            provider.add(990001, "1", picture("one", cc_by()));
+            provider.add(990001, "2", picture("two", cc_by()));
+            provider.add(990001, "3", picture("three", cc_by_sa()));
+            harvest(conn, &vec![m.clone()], provider);
+
+            let licenses = domain::dao::list_licenses(conn)
+                .into_iter()
+                .map(|l| (l.id.clone(), l))
+                .collect::<HashMap<_, _>>();
+            let pictures = domain::dao::list_pictures_by_monument_id(conn, &*m.id)
+                .into_iter()
+                .map(|p| (title(&p), licenses[&p.license_id].clone()))
+                .collect::<HashMap<_, _>>();
+            assert_eq!(pictures.len(), 3);
+
+            // the flickr licenses are shared by id, the others by name
+            assert_eq!(pictures["one"].id, pictures["two"].id);
+            assert_eq!(pictures["one"].flickr_id, Some(4));
+            assert_eq!(&*pictures["three"].provider, PROVIDER);
+            assert_eq!(&*pictures["three"].name, "CC BY-SA 4.0");
+            assert_eq!(pictures["three"].flickr_id, None);
+        });
+    }
+
+    #[test]
+    fn dedupe_against_existing_rows() {
+        test_db::with_connection(|conn| {
+            let m = monument(conn, 990002);
+            let mut provider = FixtureProvider::new(PROVIDER);
+            provider.add(990002, "1", picture("first", cc_by()));
+            harvest(conn, &vec![m.clone()], provider);
+
+            // the saved picture is found again, with other metadata
+            let mut provider = FixtureProvider::new(PROVIDER);
+            provider.add(990002, "1", picture("first again", cc_by()));
+            provider.add(990002, "2", picture("second", cc_by()));
+            harvest(conn, &vec![m.clone()], provider);
+
+            let mut titles = domain::dao::list_pictures_by_monument_id(conn, &*m.id)
+                .iter()
+                .map(title)
+                .collect::<Vec<_>>();
+            titles.sort();
+            assert_eq!(titles, vec!["first", "second"]);
+            let sizes = domain::dao::list_pictures_by_monument_id(conn, &*m.id)
+                .iter()
+                .map(|p| domain::dao::list_picture_sizes(conn, &*p.id).len())
+                .collect::<Vec<_>>();
+            assert_eq!(sizes, vec![1, 1]);
+        });
+    }
+
+    #[test]
+    fn freshness_marking() {
+        test_db::with_connection(|conn| {
+            let m = monument(conn, 990003);
+            let run = || domain::dao::list_task_runs(conn, TASK_PICTURES)
+                .into_iter()
+                .find(|r| r.monument_id == m.id)
+                .unwrap();
+            let mut provider = FixtureProvider::new(PROVIDER);
+            provider.add(990003, "1", picture("one", cc_by()));
+            harvest(conn, &vec![m.clone()], provider);
+
+            let r = run();
+            assert!(r.last_success_at.is_some());
+            assert!(r.last_attempt_at.is_some());
+            assert_eq!(r.last_error, None);
+            assert_eq!(r.empty_runs, 0);
+            let u = domain::dao::last_update_by_monument_id(conn, &*m.id).unwrap();
+            assert!(u.updated_at > u.created_at);
+            assert_eq!(u.empty_runs, 0);
+
+            // nothing new on the second harvest
+            let mut provider = FixtureProvider::new(PROVIDER);
+            provider.add(990003, "1", picture("one", cc_by()));
+            harvest(conn, &vec![m.clone()], provider);
+
+            assert_eq!(run().empty_runs, 1);
+            assert_eq!(domain::dao::last_update_by_monument_id(conn, &*m.id).unwrap().empty_runs, 1);
+        });
+    }
+}
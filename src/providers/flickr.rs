@@ -0,0 +1,123 @@
+// Copyright 2017 Jeremy Letang.
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+
+use domain::{Monument, Picture, SearchOverride};
+use flickr_api::{self, FindByLatLonError, License as RawLicense, SearchFilters};
+use std::collections::HashMap;
+use super::{Fetched, PictureProvider, ProviderLicense, Size};
+
+pub const FLICKR: &'static str = "flickr";
+
+impl From<flickr_api::Size> for Size {
+    fn from(s: flickr_api::Size) -> Size {
+        Size {
+            label: s.label,
+            width: s.width,
+            height: s.height,
+            url: s.source,
+        }
+    }
+}
+
+pub struct FlickrProvider {
+    key: String,
+    licenses: HashMap<i32, RawLicense>,
+    /// ids of the licenses of the pictures searched
+    harvested: Vec<i32>,
+}
+
+impl FlickrProvider {
+    pub fn new<S: Into<String>>(key: S, licenses: Vec<RawLicense>, harvested: Vec<i32>) -> FlickrProvider {
+        FlickrProvider {
+            key: key.into(),
+            licenses: licenses.into_iter().map(|l| (l.id, l)).collect(),
+            harvested: harvested,
+        }
+    }
+}
+
+impl PictureProvider for FlickrProvider {
+    fn name(&self) -> &str {
+        FLICKR
+    }
+
+    fn search(&self, m: &Monument, o: Option<&SearchOverride>) -> Result<Vec<String>, String> {
+        match o {
+            Some(o) => {
+                // without a query, tags or group the site name is still
+                // needed to narrow a bounding box search
+                let text = match o.query {
+                    Some(ref q) => Some(q.clone()),
+                    None if o.tags.is_empty() && o.group_id.is_none() => m.site.clone(),
+                    None => None,
+                };
+                let f = SearchFilters {
+                    text: text,
+                    tags: o.tags.clone(),
+                    bbox: o.bbox.clone(),
+                    group_id: o.group_id.clone(),
+                };
+                return flickr_api::search_photos_with(&*self.key, &f, &*self.harvested)
+                    .map(|photos| photos.into_iter().map(|p| p.id).collect());
+            },
+            None => {}
+        }
+
+        let name = match m.site {
+            Some(ref name) => name.clone(),
+            None => return Ok(vec![]), // cannot search pictures if no name
+        };
+
+        let mut pid: Option<String> = None;
+        // get place id first
+        if m.latitude.is_some() && m.longitude.is_some() {
+            pid = match flickr_api::get_place(&*self.key, m.latitude.unwrap(), m.longitude.unwrap()) {
+                Ok(pid) => Some(pid),
+                Err(e) => match e {
+                    FindByLatLonError::NoMatchingPlace => None,
+                    FindByLatLonError::RequestError(e) => return Err(e),
+                }
+            };
+        }
+
+        flickr_api::search_photos(&*self.key, name, pid, &*self.harvested)
+            .map(|photos| photos.into_iter().map(|p| p.id).collect())
+    }
+
+    fn fetch(&self, id: &str) -> Result<Fetched, String> {
+        let pi = flickr_api::get_photo_info(&*self.key, id)?;
+        let url = format!("https://farm{}.staticflickr.com/{}/{}_{}_o.jpg",
+                          pi.farm, pi.server, pi.id, pi.originalsecret);
+        let mut pic = Picture::new(FLICKR, pi.id.clone(), String::new(), String::new(),
+                                   pi.owner.username.clone(), url);
+        pic.set_info(&pi);
+
+        let sizes = match flickr_api::get_sizes(&*self.key, id) {
+            Ok(sizes) => sizes,
+            Err(e) => {
+                warn!("unable to get sizes of photo {}, {}", id, e);
+                vec![]
+            }
+        };
+        pic.set_original_size(&sizes);
+
+        let license = match self.licenses.get(&pi.license) {
+            Some(l) => ProviderLicense {
+                flickr_id: Some(l.id),
+                name: l.name.clone(),
+                url: l.url.clone(),
+            },
+            None => return Err(format!("unknown flickr license {}", pi.license)),
+        };
+
+        Ok(Fetched {
+            picture: pic,
+            license: license,
+            sizes: sizes.into_iter().map(From::from).collect(),
+        })
+    }
+}
@@ -0,0 +1,58 @@
+// Copyright 2017 Jeremy Letang.
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+
+//! The sources of the pictures of the monuments.
+
+use domain::{Monument, Picture, SearchOverride};
+
+pub mod commons;
+pub mod fixtures;
+pub mod flickr;
+
+pub use self::commons::CommonsProvider;
+pub use self::fixtures::FixtureProvider;
+pub use self::flickr::FlickrProvider;
+
+/// the license of a picture as described by its provider
+#[derive(Clone, Debug, PartialEq)]
+pub struct ProviderLicense {
+    /// only set for the pictures coming from flickr
+    pub flickr_id: Option<i32>,
+    pub name: String,
+    pub url: Option<String>,
+}
+
+/// a rendition of a picture
+#[derive(Clone, Debug, PartialEq)]
+pub struct Size {
+    pub label: String,
+    pub width: i32,
+    pub height: i32,
+    pub url: String,
+}
+
+/// a picture with everything needed to save it
+#[derive(Clone, Debug)]
+pub struct Fetched {
+    /// the `monument_id` and `license_id` of the picture are not set
+    pub picture: Picture,
+    pub license: ProviderLicense,
+    pub sizes: Vec<Size>,
+}
+
+pub trait PictureProvider {
+    /// the name saved in the `provider` column of the pictures
+    fn name(&self) -> &str;
+
+    /// the ids, for this provider, of the pictures of a monument. when the
+    /// monument has a search override it is used instead of its name and
+    /// coordinates
+    fn search(&self, m: &Monument, o: Option<&SearchOverride>) -> Result<Vec<String>, String>;
+
+    /// the metadata, license and renditions of a picture
+    fn fetch(&self, id: &str) -> Result<Fetched, String>;
+}
@@ -0,0 +1,139 @@
+// Copyright 2017 Jeremy Letang.
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+
+//! Fix the rows the constraints of the schema would now refuse, to be run
+//! before the migration adding the constraint. These commands do not
+//! check the version of the schema, they only rely on what every version
+//! has and use the newer columns when they are there.
+
+use clap::{App, ArgMatches, SubCommand};
+use cli::dry_run_arg;
+use diesel::Connection;
+use diesel::pg::PgConnection;
+use diesel::result::Error;
+use domain::{self, LastUpdate};
+use report::Report;
+use std::cmp;
+use std::collections::HashMap;
+use transaction_error;
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum Command {
+    /// merge the last updates of each monument into a single one
+    LastUpdates,
+}
+
+pub fn subcommand<'a, 'b>() -> App<'a, 'b> {
+    SubCommand::with_name("repair")
+        .about("fix the rows which prevent a migration")
+        .arg(dry_run_arg())
+        .subcommand(SubCommand::with_name("last-updates")
+                    .about("merge the last updates of a monument into the most recent one"))
+}
+
+pub fn from_matches(matches: &ArgMatches) -> Result<Command, String> {
+    match matches.subcommand() {
+        ("last-updates", Some(_)) => Ok(Command::LastUpdates),
+        _ => Err(format!("missing repair command, see --help")),
+    }
+}
+
+pub fn run(conn: &PgConnection, cmd: Command, report: &mut Report) -> Result<(), String> {
+    match cmd {
+        Command::LastUpdates => merge_last_updates(conn, report),
+    }
+}
+
+/// the last updates of a monument are merged into the most recent one,
+/// the one the refreshes were reading. it takes the earliest creation and
+/// the most empty runs, a backoff is never shortened by the repair
+fn merge_last_updates(conn: &PgConnection, report: &mut Report) -> Result<(), String> {
+    let with_runs = domain::dao::last_updates_have_empty_runs(conn);
+    let mut by_monument: HashMap<String, Vec<LastUpdate>> = HashMap::new();
+    for u in domain::dao::list_last_updates(conn, with_runs) {
+        by_monument.entry(u.monument_id.clone()).or_insert(vec![]).push(u);
+    }
+
+    let mut merges = vec![];
+    for (mid, mut updates) in by_monument {
+        if updates.len() < 2 {
+            continue
+        }
+        updates.sort_by(|a, b| b.updated_at.cmp(&a.updated_at));
+        let mut kept = updates[0].clone();
+        for u in &updates[1..] {
+            report.delete("last_updates", mid.clone(), format!("merged into {}, created at {}, {} empty runs",
+                                                               kept.id, u.created_at, u.empty_runs));
+            kept.created_at = cmp::min(kept.created_at, u.created_at);
+            kept.empty_runs = cmp::max(kept.empty_runs, u.empty_runs);
+        }
+        report.update("last_updates", mid.clone(), format!("created at {}, {} empty runs",
+                                                           kept.created_at, kept.empty_runs));
+        let merged = updates[1..].iter().map(|u| u.id.clone()).collect::<Vec<_>>();
+        merges.push((kept, merged));
+    }
+
+    info!("{} monuments with duplicate last updates", merges.len());
+    if report.dry_run || merges.is_empty() {
+        return Ok(())
+    }
+    // all the monuments are repaired or none
+    conn.transaction::<_, Error, _>(|| {
+        for &(ref kept, ref merged) in &merges {
+            domain::dao::merge_last_updates(conn, kept, merged, with_runs)?;
+        }
+        Ok(())
+    }).map_err(|e| format!("unable to merge the duplicate last updates, {}", transaction_error(e)))?;
+    info!("{} last updates merged", merges.iter().map(|&(_, ref m)| m.len()).sum::<usize>());
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::NaiveDateTime;
+    use diesel::{self, ExecuteDsl};
+    use domain::{self, LastUpdate, Monument};
+    use domain::schema::last_updates;
+    use migrate;
+    use report::Report;
+    use super::*;
+    use test_db;
+
+    fn day(n: i64) -> NaiveDateTime {
+        NaiveDateTime::from_timestamp(n * 86400, 0)
+    }
+
+    #[test]
+    fn merge_into_the_most_recent() {
+        test_db::with_connection(|conn| {
+            // before the unique last update of the monuments
+            migrate::down(conn, Some("20170606090000")).unwrap();
+            let mut m = Monument::new();
+            m.id_number = Some(990201);
+            ::insert_monuments(conn, vec![m], false, &mut Report::new(false)).unwrap();
+            let m = domain::dao::find_monument_by_id_number(conn, 990201).unwrap();
+
+            let mut recent = LastUpdate::new(&*m.id);
+            recent.updated_at = day(10);
+            recent.empty_runs = 1;
+            let mut old = LastUpdate::new(&*m.id);
+            old.created_at = day(1);
+            old.updated_at = day(5);
+            old.empty_runs = 4;
+            diesel::insert(&vec![recent.clone(), old]).into(last_updates::table).execute(conn).unwrap();
+
+            let mut report = Report::new(false);
+            run(conn, Command::LastUpdates, &mut report).unwrap();
+            assert_eq!(report.counts("last_updates"), (0, 1, 2));
+            let updates = domain::dao::list_last_updates(conn, true)
+                .into_iter()
+                .filter(|u| u.monument_id == m.id)
+                .collect::<Vec<_>>();
+            assert_eq!(updates, vec![LastUpdate { created_at: day(1), empty_runs: 4, ..recent }]);
+        });
+    }
+}
@@ -0,0 +1,102 @@
+// Copyright 2017 Jeremy Letang.
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+
+//! The changes made, or that would be made in a dry run, to each table.
+//!
+//! In a dry run the steps still do every read and remote call, but record
+//! their writes here instead of sending them to the database.
+
+use std::collections::BTreeMap;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Kind {
+    Insert,
+    Update,
+    Delete,
+}
+
+impl Kind {
+    fn sign(&self) -> &'static str {
+        match *self {
+            Kind::Insert => "+",
+            Kind::Update => "~",
+            Kind::Delete => "-",
+        }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct Change {
+    pub kind: Kind,
+    /// what identifies the row for a human, id number, picture id...
+    pub key: String,
+    pub detail: String,
+}
+
+#[derive(Clone, Debug)]
+pub struct Report {
+    pub dry_run: bool,
+    changes: BTreeMap<&'static str, Vec<Change>>,
+}
+
+impl Report {
+    pub fn new(dry_run: bool) -> Report {
+        Report {
+            dry_run: dry_run,
+            changes: BTreeMap::new(),
+        }
+    }
+
+    pub fn record<K, D>(&mut self, table: &'static str, kind: Kind, key: K, detail: D)
+        where K: Into<String>, D: Into<String> {
+        self.changes.entry(table).or_insert(vec![]).push(Change {
+            kind: kind,
+            key: key.into(),
+            detail: detail.into(),
+        });
+    }
+
+    pub fn insert<K: Into<String>, D: Into<String>>(&mut self, table: &'static str, key: K, detail: D) {
+        self.record(table, Kind::Insert, key, detail);
+    }
+
+    pub fn update<K: Into<String>, D: Into<String>>(&mut self, table: &'static str, key: K, detail: D) {
+        self.record(table, Kind::Update, key, detail);
+    }
+
+    pub fn delete<K: Into<String>, D: Into<String>>(&mut self, table: &'static str, key: K, detail: D) {
+        self.record(table, Kind::Delete, key, detail);
+    }
+
+    /// whether a change of this kind was recorded for the row, like the
+    /// insert of a monument a dry run would make
+    pub fn contains(&self, table: &str, kind: Kind, key: &str) -> bool {
+        self.changes.get(table).map_or(false, |cs| cs.iter().any(|c| c.kind == kind && c.key == key))
+    }
+
+    /// number of inserts, updates and deletes of a table
+    pub fn counts(&self, table: &str) -> (usize, usize, usize) {
+        let cs = self.changes.get(table).map_or(&[][..], |cs| &cs[..]);
+        let n = |k| cs.iter().filter(|c| c.kind == k).count();
+        (n(Kind::Insert), n(Kind::Update), n(Kind::Delete))
+    }
+
+    /// print the changes as a diff, table by table
+    pub fn print(&self) {
+        if self.changes.is_empty() {
+            println!("no change");
+            return
+        }
+        for (table, changes) in &self.changes {
+            let (i, u, d) = self.counts(table);
+            println!("{}: {} inserts, {} updates, {} deletes", table, i, u, d);
+            for c in changes {
+                println!("{} {}\t{}", c.kind.sign(), c.key, c.detail);
+            }
+        }
+    }
+}
@@ -0,0 +1,67 @@
+// Copyright 2017 Jeremy Letang.
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+
+//! Pick the monuments on which an enrichment task is due, and record the
+//! outcome of each run in the `task_runs` table.
+
+use diesel::pg::PgConnection;
+use domain::{self, Monument, TaskRun};
+use freshness::Policy;
+use report::Report;
+use std::collections::HashMap;
+
+/// the monuments on which `task` is due with their run, the ones on which
+/// the task never succeeded first, then the least recently refreshed
+pub fn due<'a>(conn: &PgConnection, task: &str, monuments: &'a [Monument], policy: &Policy)
+               -> Vec<(&'a Monument, TaskRun)> {
+    let mut runs = domain::dao::list_task_runs(conn, task)
+        .into_iter()
+        .map(|r| (r.monument_id.clone(), r))
+        .collect::<HashMap<_, _>>();
+
+    let mut due = monuments.iter()
+        .filter_map(|m| {
+            let r = runs.remove(&m.id).unwrap_or(TaskRun::new(&*m.id, task));
+            if policy.need_refresh(m, &r) {
+                Some((m, r))
+            } else {
+                debug!("task {} of monument {} is not due, skip it", task, m.id);
+                None
+            }
+        })
+        .collect::<Vec<_>>();
+    due.sort_by_key(|&(_, ref r)| r.last_success_at);
+
+    info!("task {} is due on {} monuments", task, due.len());
+    due
+}
+
+pub fn start(conn: &PgConnection, r: &mut TaskRun, report: &mut Report) {
+    r.attempt();
+    save(conn, r, report, "attempt");
+}
+
+pub fn succeed(conn: &PgConnection, r: &mut TaskRun, found: usize, report: &mut Report) {
+    r.succeed(found);
+    save(conn, r, report, "success");
+}
+
+pub fn fail<S: Into<String>>(conn: &PgConnection, r: &mut TaskRun, error: S, report: &mut Report) {
+    r.fail(error);
+    save(conn, r, report, "failure");
+}
+
+fn save(conn: &PgConnection, r: &TaskRun, report: &mut Report, what: &str) {
+    report.update("task_runs", format!("{} {}", r.task, r.monument_id), what);
+    if report.dry_run {
+        return
+    }
+    match domain::dao::save_task_run(conn, r) {
+        Ok(_) => {},
+        Err(e) => panic!(format!("{}", e)),
+    }
+}
@@ -0,0 +1,48 @@
+// Copyright 2017 Jeremy Letang.
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+
+//! Counts of what the database contains.
+
+use diesel::pg::PgConnection;
+use domain;
+use std::collections::{BTreeMap, HashSet};
+
+pub fn run(conn: &PgConnection) -> Result<(), String> {
+    let monuments = domain::dao::list_monuments(conn);
+    let components = domain::dao::list_monument_components(conn);
+    let pictures = domain::dao::list_pictures(conn);
+    let licenses = domain::dao::list_licenses(conn);
+    let runs = domain::dao::list_task_runs(conn, domain::TASK_PICTURES);
+
+    let illustrated = pictures.iter()
+        .filter(|p| &*p.status == domain::PICTURE_ACTIVE)
+        .map(|p| &*p.monument_id)
+        .collect::<HashSet<_>>();
+    let serial = components.iter()
+        .map(|c| &*c.monument_id)
+        .collect::<HashSet<_>>();
+    let mut by_provider = BTreeMap::new();
+    for p in &pictures {
+        *by_provider.entry((&*p.provider, &*p.status)).or_insert(0) += 1;
+    }
+
+    println!("monuments\t{}", monuments.len());
+    println!("monuments with pictures\t{}", illustrated.len());
+    println!("serial monuments\t{}", serial.len());
+    println!("monument components\t{}", components.len());
+    println!("licenses\t{}", licenses.len());
+    println!("pictures\t{}", pictures.len());
+    for ((provider, status), n) in by_provider {
+        println!("pictures {} {}\t{}", provider, status, n);
+    }
+    println!("duplicate pictures\t{}", pictures.iter().filter(|p| p.duplicate_of.is_some()).count());
+    println!("pictures with placeholder\t{}", pictures.iter().filter(|p| p.blurhash.is_some()).count());
+    println!("picture harvests never done\t{}",
+             monuments.len().saturating_sub(runs.iter().filter(|r| r.last_success_at.is_some()).count()));
+    println!("picture harvests failed\t{}", runs.iter().filter(|r| r.last_error.is_some()).count());
+    Ok(())
+}
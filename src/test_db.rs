@@ -0,0 +1,40 @@
+// Copyright 2017 Jeremy Letang.
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+
+//! The database of the tests, given by `WHLP_TEST_DATABASE_URL`.
+//!
+//! The tests using it are skipped when the variable is not set. Each one
+//! runs alone, in a transaction rolled back at the end, so the database is
+//! left as it was found.
+
+use diesel::Connection;
+use diesel::pg::PgConnection;
+use establish_connection;
+use migrate;
+use std::env;
+use std::sync::Mutex;
+
+lazy_static! {
+    static ref LOCK: Mutex<()> = Mutex::new(());
+}
+
+/// run `f` on the migrated test database, do nothing if there is none
+pub fn with_connection<F: FnOnce(&PgConnection)>(f: F) {
+    let url = match env::var("WHLP_TEST_DATABASE_URL") {
+        Ok(url) => url,
+        Err(_) => {
+            println!("WHLP_TEST_DATABASE_URL is not set, skipping the test");
+            return
+        }
+    };
+    // a failed test poisons the lock, the next ones can still run
+    let _lock = LOCK.lock().unwrap_or_else(|e| e.into_inner());
+    let conn = establish_connection(&*url);
+    conn.begin_test_transaction().unwrap();
+    migrate::up(&conn, None).unwrap();
+    f(&conn);
+}
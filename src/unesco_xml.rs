@@ -5,7 +5,7 @@
 // option. This file may not be copied, modified, or distributed
 // except according to those terms.
 
-use hyper::{self, Client};
+use http;
 use std::error::Error;
 use std::fs::File;
 use std::io::Read;
@@ -35,18 +35,5 @@ pub fn from_file(file: String) -> Result<String, String> {
 
 pub fn from_download() -> Result<String, String> {
     info!("downloading unesco xml");
-    match Client::new().get(UNESCO_XML).send() {
-        Ok(mut r) => {
-            if r.status == hyper::Ok {
-                let mut buf = String::new();
-                match r.read_to_string(&mut buf) {
-                    Ok(_) => Ok(buf),
-                    Err(e) => Err(format!("unable to read http request payload, try again, {}", e))
-                }
-            } else {
-                Err(format!("unexpected http status, try again"))
-            }
-        },
-        Err(e) => Err(format!("unable to get whc xml, {}", e.description()))
-    }
+    http::get_body(UNESCO_XML, "whc xml")
 }
@@ -0,0 +1,164 @@
+// Copyright 2017 Jeremy Letang.
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+
+//! A lossless webp encoder, the webp crates needing a newer toolchain.
+//!
+//! The pixels are written without transform nor backward reference, with
+//! a fixed 8 bits code for each channel, so the files are about the size
+//! of a bitmap. Lossy webp needs a vp8 encoder, there is no quality here.
+
+use std::cmp;
+
+/// the longest side of a webp image
+pub const MAX_SIZE: u32 = 16384;
+
+/// the order in which the lengths of the code length code are written
+const CODE_LENGTH_ORDER: [usize; 19] = [17, 18, 0, 1, 2, 3, 4, 5, 16, 6, 7, 8, 9, 10, 11, 12, 13, 14, 15];
+
+/// the bits of the stream, from the lowest of each byte
+struct BitWriter {
+    buf: Vec<u8>,
+    bits: u64,
+    used: u32,
+}
+
+impl BitWriter {
+    fn new() -> BitWriter {
+        BitWriter { buf: vec![], bits: 0, used: 0 }
+    }
+
+    fn write(&mut self, value: u32, n: u32) {
+        self.bits |= (value as u64) << self.used;
+        self.used += n;
+        while self.used >= 8 {
+            self.buf.push(self.bits as u8);
+            self.bits >>= 8;
+            self.used -= 8;
+        }
+    }
+
+    fn finish(mut self) -> Vec<u8> {
+        if self.used > 0 {
+            self.buf.push(self.bits as u8);
+        }
+        self.buf
+    }
+}
+
+/// a code of a single symbol, which takes no bit in the pixels
+fn single_symbol_code(w: &mut BitWriter, symbol: u8) {
+    w.write(1, 1);
+    w.write(0, 1);
+    w.write(1, 1);
+    w.write(symbol as u32, 8);
+}
+
+/// a code giving 8 bits to the first 256 symbols of an alphabet of `size`
+/// symbols, the others are never used. the lengths are written with a code
+/// of a bit, 0 for a length of 8 and 1 for a run of zeros
+fn byte_code(w: &mut BitWriter, size: usize) {
+    w.write(0, 1);
+    // the lengths up to the one of the symbol 8
+    let n = CODE_LENGTH_ORDER.iter().position(|&s| s == 8).unwrap() + 1;
+    w.write(n as u32 - 4, 4);
+    for &s in CODE_LENGTH_ORDER[..n].iter() {
+        w.write(if s == 8 || s == 18 { 1 } else { 0 }, 3);
+    }
+    // no max symbol, the lengths of the whole alphabet follow
+    w.write(0, 1);
+    for _ in 0..256 {
+        w.write(0, 1);
+    }
+    let mut zeros = size - 256;
+    while zeros > 0 {
+        // a run of 11 to 138 zeros
+        let run = cmp::min(zeros, 138);
+        w.write(1, 1);
+        w.write(run as u32 - 11, 7);
+        zeros -= run;
+    }
+}
+
+/// the canonical code of a byte is the byte, written from its highest bit
+fn write_byte(w: &mut BitWriter, b: u8) {
+    let reversed = (0..8).fold(0, |r, i| r | (((b as u32) >> i) & 1) << (7 - i));
+    w.write(reversed, 8);
+}
+
+/// encode rgb pixels, 3 bytes by pixel, as a webp file
+pub fn encode(rgb: &[u8], width: u32, height: u32) -> Result<Vec<u8>, String> {
+    if width == 0 || height == 0 || width > MAX_SIZE || height > MAX_SIZE {
+        return Err(format!("invalid webp size {}x{}", width, height))
+    }
+    if rgb.len() != (width * height * 3) as usize {
+        return Err(format!("{} bytes for {}x{} rgb pixels", rgb.len(), width, height))
+    }
+
+    let mut w = BitWriter::new();
+    w.write(0x2f, 8);
+    w.write(width - 1, 14);
+    w.write(height - 1, 14);
+    // no alpha, version 0
+    w.write(0, 1);
+    w.write(0, 3);
+    // no transform, no color cache, a single set of codes
+    w.write(0, 1);
+    w.write(0, 1);
+    w.write(0, 1);
+    // green with the 24 length prefixes, red, blue, alpha and distance
+    byte_code(&mut w, 256 + 24);
+    byte_code(&mut w, 256);
+    byte_code(&mut w, 256);
+    single_symbol_code(&mut w, 0xff);
+    single_symbol_code(&mut w, 0);
+    for p in rgb.chunks(3) {
+        write_byte(&mut w, p[1]);
+        write_byte(&mut w, p[0]);
+        write_byte(&mut w, p[2]);
+    }
+    let mut vp8l = w.finish();
+    let size = vp8l.len() as u32;
+    if vp8l.len() % 2 == 1 {
+        vp8l.push(0);
+    }
+
+    let mut out = Vec::with_capacity(vp8l.len() + 20);
+    out.extend_from_slice(b"RIFF");
+    out.extend_from_slice(&le32(vp8l.len() as u32 + 12));
+    out.extend_from_slice(b"WEBPVP8L");
+    out.extend_from_slice(&le32(size));
+    out.extend_from_slice(&vp8l);
+    Ok(out)
+}
+
+fn le32(n: u32) -> [u8; 4] {
+    [n as u8, (n >> 8) as u8, (n >> 16) as u8, (n >> 24) as u8]
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn header() {
+        let webp = encode(&[255, 0, 0, 0, 255, 0], 2, 1).unwrap();
+        assert_eq!(&webp[0..4], b"RIFF");
+        assert_eq!(le32(webp.len() as u32 - 8), [webp[4], webp[5], webp[6], webp[7]]);
+        assert_eq!(&webp[8..16], b"WEBPVP8L");
+        assert_eq!(webp[20], 0x2f);
+        // 14 bits of width - 1, then 14 bits of height - 1
+        assert_eq!(&webp[21..25], &[1, 0, 0, 0]);
+        assert_eq!(webp.len() % 2, 0);
+    }
+
+    #[test]
+    fn invalid_sizes() {
+        assert!(encode(&[], 0, 1).is_err());
+        assert!(encode(&[0; 6], 1, 1).is_err());
+        assert!(encode(&[0; 3], MAX_SIZE + 1, 1).is_err());
+    }
+}